@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use teloxide::{
+    Bot,
+    prelude::Requester,
+    types::{CallbackQuery, ChatId, Message, User},
+};
+use tokio::{
+    select,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    time::sleep,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::{
+    config::{AlertSettings, Config},
+    pylon::PylonError,
+};
+
+/// What the bot was doing when an error happened, attached to errors with `wrap_err`.
+#[derive(Debug, Clone, Default)]
+pub struct AlertContext {
+    pub chat_id: Option<i64>,
+    pub user: Option<String>,
+    pub command: Option<String>,
+}
+
+impl AlertContext {
+    pub fn for_message(message: &Message) -> Self {
+        Self {
+            chat_id: Some(message.chat.id.0),
+            user: message.from.as_ref().map(describe_user),
+            command: command_name(message).map(str::to_string),
+        }
+    }
+
+    pub fn for_callback(query: &CallbackQuery) -> Self {
+        Self {
+            chat_id: query.message.as_ref().map(|message| message.chat().id.0),
+            user: Some(describe_user(&query.from)),
+            command: query.data.as_ref().map(|data| format!("button {data}")),
+        }
+    }
+}
+
+/// Returns the name of the command in `message`, without the bot username.
+pub fn command_name(message: &Message) -> Option<&str> {
+    message
+        .text()
+        .filter(|text| text.starts_with('/'))
+        .and_then(|text| text.split_whitespace().next())
+        .and_then(|command| command.split('@').next())
+}
+
+fn describe_user(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{username} ({})", user.id),
+        None => user.id.to_string(),
+    }
+}
+
+impl fmt::Display for AlertContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+
+        if let Some(command) = &self.command {
+            parts.push(format!("command {command}"));
+        }
+        if let Some(chat_id) = self.chat_id {
+            parts.push(format!("chat {chat_id}"));
+        }
+        if let Some(user) = &self.user {
+            parts.push(format!("user {user}"));
+        }
+
+        write!(f, "While handling {}", parts.join(", "))
+    }
+}
+
+#[derive(Debug)]
+pub struct Alert {
+    message: String,
+    context: Option<AlertContext>,
+    pylon_request_id: Option<String>,
+}
+
+/// Sends errors to the [`Alerter`].
+#[derive(Clone)]
+pub struct Alerts {
+    sender: UnboundedSender<Alert>,
+}
+
+impl Alerts {
+    pub fn new() -> (Self, UnboundedReceiver<Alert>) {
+        let (sender, receiver) = unbounded_channel();
+
+        (Self { sender }, receiver)
+    }
+
+    /// Reports an error, with the context and Pylon request id found in its chain.
+    pub fn report(&self, err: &eyre::Report) {
+        let context = err.downcast_ref::<AlertContext>().cloned();
+        // The context is the outermost error of the chain
+        let message = err
+            .chain()
+            .skip(usize::from(context.is_some()))
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(": ");
+
+        let _ = self.sender.send(Alert {
+            message,
+            context,
+            pylon_request_id: err
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<PylonError>())
+                .and_then(|err| err.request_id.clone()),
+        });
+    }
+
+    /// Reports an error without context.
+    pub fn send(&self, message: impl Into<String>) {
+        let _ = self.sender.send(Alert {
+            message: message.into(),
+            context: None,
+            pylon_request_id: None,
+        });
+    }
+}
+
+/// Forwards errors to the admin chats, and sends them a daily digest.
+pub struct Alerter {
+    bot: Bot,
+    config: Arc<Config>,
+    /// When each error sent within `dedup_minutes` was last sent.
+    last_sent: HashMap<String, DateTime<Utc>>,
+    /// When the alerts of the last hour were sent.
+    sent: VecDeque<DateTime<Utc>>,
+    /// Number of occurrences of each error since the last digest, if digests are sent.
+    digest: BTreeMap<String, usize>,
+}
+
+impl Alerter {
+    pub fn new(bot: Bot, config: Arc<Config>) -> Self {
+        Self {
+            bot,
+            config,
+            last_sent: HashMap::new(),
+            sent: VecDeque::new(),
+            digest: BTreeMap::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut alerts: UnboundedReceiver<Alert>, token: CancellationToken) {
+        loop {
+            // Settings are read on each alert to pick up reloads
+            let settings = self.config.get().await.alerts;
+            let until_digest = settings
+                .digest_time
+                .map(|time| until(time, Utc::now()))
+                .unwrap_or(Duration::MAX);
+
+            select! {
+                _ = token.cancelled() => {
+                    break;
+                }
+                Some(alert) = alerts.recv() => {
+                    self.alert(alert, &settings).await;
+                }
+                _ = sleep(until_digest) => {
+                    self.send_digest(&settings).await;
+                }
+            }
+        }
+    }
+
+    async fn alert(&mut self, alert: Alert, settings: &AlertSettings) {
+        let now = Utc::now();
+        let dedup = TimeDelta::minutes(settings.dedup_minutes as i64);
+
+        // Errors are only counted when a digest will send them
+        if settings.digest_time.is_some() {
+            *self.digest.entry(alert.message.clone()).or_default() += 1;
+        }
+
+        self.last_sent.retain(|_, sent| now - *sent < dedup);
+
+        // Same error sent recently
+        if self.last_sent.contains_key(&alert.message) {
+            return;
+        }
+
+        self.sent.retain(|sent| now - *sent < TimeDelta::hours(1));
+
+        if self.sent.len() >= settings.max_per_hour {
+            return;
+        }
+
+        self.sent.push_back(now);
+        self.last_sent.insert(alert.message.clone(), now);
+
+        let mut text = format!("⚠️ Error: {}", alert.message);
+
+        if let Some(context) = &alert.context {
+            if let Some(command) = &context.command {
+                text.push_str(&format!("\nCommand: {command}"));
+            }
+            if let Some(chat_id) = context.chat_id {
+                text.push_str(&format!("\nChat: {chat_id}"));
+            }
+            if let Some(user) = &context.user {
+                text.push_str(&format!("\nUser: {user}"));
+            }
+        }
+
+        if let Some(request_id) = &alert.pylon_request_id {
+            text.push_str(&format!("\nPylon request: {request_id}"));
+        }
+
+        self.send(&text, settings).await;
+    }
+
+    async fn send_digest(&mut self, settings: &AlertSettings) {
+        let digest = std::mem::take(&mut self.digest);
+
+        if digest.is_empty() {
+            return;
+        }
+
+        let mut errors = digest.into_iter().collect::<Vec<_>>();
+        errors.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let total = errors.iter().map(|(_, count)| count).sum::<usize>();
+        let mut text = format!("📋 {total} error(s) since the last digest:");
+
+        for (message, count) in errors.iter().take(20) {
+            text.push_str(&format!("\n{count} × {message}"));
+        }
+
+        if errors.len() > 20 {
+            text.push_str(&format!("\n... and {} other errors", errors.len() - 20));
+        }
+
+        self.send(&text, settings).await;
+    }
+
+    async fn send(&self, text: &str, settings: &AlertSettings) {
+        for chat_id in &settings.chat_ids {
+            if let Err(err) = self.bot.send_message(ChatId(*chat_id), text).await {
+                warn!("Failed to send alert to {chat_id}: {err}");
+            }
+        }
+    }
+}
+
+/// Returns the time until the next `time` of day.
+fn until(time: NaiveTime, now: DateTime<Utc>) -> Duration {
+    let today = now.date_naive().and_time(time).and_utc();
+    let next = if today > now {
+        today
+    } else {
+        today + TimeDelta::days(1)
+    };
+
+    (next - now).to_std().unwrap_or_default()
+}
@@ -0,0 +1,220 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+use tracing::warn;
+
+/// Administrative or issue action recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Link,
+    Unlink,
+    AdminAdd,
+    AdminRemove,
+    SettingsChange,
+    SettingsReload,
+    IssueCreate,
+    IssueClose,
+    IssueReopen,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Link => "link",
+            AuditAction::Unlink => "unlink",
+            AuditAction::AdminAdd => "admin_add",
+            AuditAction::AdminRemove => "admin_remove",
+            AuditAction::SettingsChange => "settings_change",
+            AuditAction::SettingsReload => "settings_reload",
+            AuditAction::IssueCreate => "issue_create",
+            AuditAction::IssueClose => "issue_close",
+            AuditAction::IssueReopen => "issue_reopen",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Telegram id of the user who performed the action, none for the bot or operators.
+    pub actor: Option<u64>,
+    pub action: AuditAction,
+    /// What the action applies to, such as a chat id or an issue number.
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(actor: Option<u64>, action: AuditAction, target: impl Into<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            actor,
+            action,
+            target: target.into(),
+            details: None,
+        }
+    }
+
+    pub fn details(self, details: impl Into<String>) -> Self {
+        Self {
+            details: Some(details.into()),
+            ..self
+        }
+    }
+
+    /// Whether `filter` appears, ignoring case, in the actor, action, target or details.
+    pub fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+
+        [
+            self.actor.map(|actor| actor.to_string()),
+            Some(self.action.to_string()),
+            Some(self.target.clone()),
+            self.details.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(&filter))
+    }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.action,
+            self.target
+        )?;
+
+        if let Some(details) = &self.details {
+            write!(f, " ({details})")?;
+        }
+
+        match self.actor {
+            Some(actor) => write!(f, " by {actor}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Append-only log of the administrative and issue actions, one JSON entry per line.
+pub struct AuditLog {
+    path: PathBuf,
+    /// Serializes the writes so that entries are not interleaved.
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn record(&self, entry: AuditEntry) -> eyre::Result<()> {
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let _lock = self.lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    /// Returns all entries, oldest first. Lines that can't be parsed, such as a line cut short by
+    /// a crash, are skipped.
+    pub async fn entries(&self) -> eyre::Result<Vec<AuditEntry>> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(index, line)| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    warn!(
+                        "Skipped audit log line {} of {}: {err}",
+                        index + 1,
+                        self.path.display()
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+}
+
+/// Writes entries as JSON lines.
+pub fn write_jsonl(entries: &[AuditEntry], mut writer: impl Write) -> eyre::Result<()> {
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Writes entries as CSV, with a header.
+pub fn write_csv(entries: &[AuditEntry], mut writer: impl Write) -> eyre::Result<()> {
+    writeln!(writer, "timestamp,actor,action,target,details")?;
+
+    for entry in entries {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            entry.timestamp.to_rfc3339(),
+            entry
+                .actor
+                .map(|actor| actor.to_string())
+                .unwrap_or_default(),
+            entry.action,
+            csv_field(&entry.target),
+            csv_field(entry.details.as_deref().unwrap_or_default())
+        )?;
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
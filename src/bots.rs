@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use eyre::{OptionExt, WrapErr, bail};
+use teloxide::{Bot, prelude::Requester};
+use tracing::warn;
+
+use crate::{
+    config::{DEFAULT_BOT, Settings},
+    secret::Secret,
+};
+
+/// Name and Telegram username of the bot receiving an update.
+#[derive(Debug, Clone)]
+pub struct BotIdentity {
+    pub name: String,
+    pub username: String,
+}
+
+/// Telegram bots run by the process, by name.
+///
+/// Bots are read at startup, adding one to the settings requires a restart.
+pub struct Bots {
+    bots: BTreeMap<String, (Bot, BotIdentity)>,
+}
+
+impl Bots {
+    /// Creates the bots in `settings`, and the default bot if `default_token` is set.
+    ///
+    /// The username of each bot is asked to Telegram, so that it always matches its token.
+    pub async fn try_new(settings: &Settings, default_token: Option<Secret>) -> eyre::Result<Self> {
+        let mut tokens = BTreeMap::new();
+
+        if let Some(token) = default_token {
+            tokens.insert(DEFAULT_BOT.to_string(), token);
+        }
+
+        for (name, bot) in &settings.bots {
+            if tokens.contains_key(name) {
+                bail!("Bot '{name}' is also set with TELOXIDE_TOKEN");
+            }
+
+            let token = bot
+                .token()
+                .wrap_err_with(|| format!("Invalid bot '{name}'"))?;
+
+            tokens.insert(name.clone(), token);
+        }
+
+        if !tokens.contains_key(DEFAULT_BOT) {
+            bail!(
+                "TELOXIDE_TOKEN, --telegram-token-file or a bot named '{DEFAULT_BOT}' in the settings is required"
+            );
+        }
+
+        let mut bots = BTreeMap::new();
+
+        for (name, token) in tokens {
+            let bot = Bot::new(token.expose());
+            let me = bot
+                .get_me()
+                .await
+                .wrap_err_with(|| format!("Failed to get the username of bot '{name}'"))?;
+            let username = me.username().to_string();
+
+            if let Some(expected) = settings
+                .bots
+                .get(&name)
+                .and_then(|bot| bot.username.as_deref())
+                && expected.trim_start_matches('@') != username
+            {
+                warn!("Bot '{name}' is @{username}, not @{expected} as set in the settings");
+            }
+
+            bots.insert(name.clone(), (bot, BotIdentity { name, username }));
+        }
+
+        Ok(Self { bots })
+    }
+
+    /// Returns the bot used for the chats that don't have one, such as private chats with admins.
+    pub fn default_bot(&self) -> Bot {
+        self.bots[DEFAULT_BOT].0.clone()
+    }
+
+    /// Returns the bot added to `chat_id`.
+    pub fn for_chat(&self, settings: &Settings, chat_id: &str) -> eyre::Result<Bot> {
+        let name = settings.bot(chat_id);
+
+        self.bots
+            .get(&name)
+            .map(|(bot, _)| bot.clone())
+            .ok_or_eyre(format!("Unknown bot '{name}'"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Bot, BotIdentity)> {
+        self.bots.values()
+    }
+}
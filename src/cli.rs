@@ -1,14 +1,260 @@
-use clap::Parser;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::Url;
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+    config::DEFAULT_WORKSPACE,
+    logging::{LogFormat, LogRotation},
+    secret::Secret,
+};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+
+    /// API token of the default Pylon workspace
+    #[clap(
+        long,
+        env,
+        hide_env_values = true,
+        conflicts_with = "pylon_api_token_file"
+    )]
+    pub pylon_api_token: Option<Secret>,
+
+    /// File holding the API token of the default Pylon workspace
+    #[clap(long, env)]
+    pub pylon_api_token_file: Option<PathBuf>,
+
+    /// Token of the default Telegram bot
+    #[clap(
+        long,
+        env = "TELOXIDE_TOKEN",
+        hide_env_values = true,
+        conflicts_with = "telegram_token_file"
+    )]
+    pub telegram_token: Option<Secret>,
+
+    /// File holding the token of the default Telegram bot
     #[clap(long, env)]
-    pub pylon_api_token: String,
+    pub telegram_token_file: Option<PathBuf>,
 
     #[clap(long, env)]
     pub settings_path: Option<String>,
 
+    #[clap(long, env)]
+    pub storage_path: Option<String>,
+
+    /// Path to the audit log (default: `./audit.jsonl`)
+    #[clap(long, env)]
+    pub audit_path: Option<String>,
+
     #[clap(long, env)]
     pub logs_path: Option<String>,
+
+    #[clap(long, env, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+
+    /// When log files are rotated
+    #[clap(long, env, value_enum, default_value_t)]
+    pub log_rotation: LogRotation,
+
+    /// Size in MB after which log files are rotated
+    #[clap(long, env)]
+    pub log_max_size_mb: Option<u64>,
+
+    /// Number of log files kept, including the current one
+    #[clap(long, env, default_value_t = 7)]
+    pub log_max_files: usize,
+
+    /// Level of the logs written to files
+    #[clap(long, env, default_value_t = LevelFilter::INFO)]
+    pub log_file_level: LevelFilter,
+
+    /// Write the text of messages to the logs
+    #[clap(long, env)]
+    pub log_message_text: bool,
+
+    /// Address to receive Pylon webhooks on, webhooks are disabled if not set
+    #[clap(
+        long,
+        env,
+        requires_all = ["pylon_webhook_secret", "experimental_pylon_webhooks"]
+    )]
+    pub webhook_address: Option<SocketAddr>,
+
+    /// Accept Pylon webhooks, whose format has not been checked against Pylon's documentation
+    #[clap(long, env)]
+    pub experimental_pylon_webhooks: bool,
+
+    /// Secret Pylon webhooks are signed with
+    #[clap(long, env, hide_env_values = true)]
+    pub pylon_webhook_secret: Option<Secret>,
+
+    /// Address to serve `/healthz`, `/readyz` and `/metrics` on
+    #[clap(long, env)]
+    pub monitoring_address: Option<SocketAddr>,
+
+    /// Public URL Telegram sends updates to, updates are polled if not set
+    #[clap(long, env, requires = "telegram_webhook_address")]
+    pub telegram_webhook_url: Option<Url>,
+
+    /// Address to receive Telegram updates on
+    #[clap(long, env)]
+    pub telegram_webhook_address: Option<SocketAddr>,
+
+    /// Secret Telegram sends with each update, generated if not set
+    #[clap(long, env, hide_env_values = true, value_parser = parse_secret_token)]
+    pub telegram_webhook_secret: Option<Secret>,
+
+    /// Public key certificate to upload to Telegram, when using a self-signed certificate
+    #[clap(long, env)]
+    pub telegram_webhook_certificate: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn settings_path(&self) -> String {
+        self.settings_path
+            .clone()
+            .unwrap_or_else(|| "./settings.toml".to_string())
+    }
+
+    pub fn storage_path(&self) -> String {
+        self.storage_path
+            .clone()
+            .unwrap_or_else(|| "./storage.toml".to_string())
+    }
+
+    pub fn audit_path(&self) -> String {
+        self.audit_path
+            .clone()
+            .unwrap_or_else(|| "./audit.jsonl".to_string())
+    }
+
+    /// Returns the API token of the default Pylon workspace, given directly or in a file.
+    pub fn pylon_api_token(&self) -> eyre::Result<Option<Secret>> {
+        read_secret(&self.pylon_api_token, self.pylon_api_token_file.as_deref())
+    }
+
+    /// Returns the token of the default Telegram bot, given directly or in a file.
+    pub fn telegram_token(&self) -> eyre::Result<Option<Secret>> {
+        read_secret(&self.telegram_token, self.telegram_token_file.as_deref())
+    }
+}
+
+fn read_secret(secret: &Option<Secret>, path: Option<&Path>) -> eyre::Result<Option<Secret>> {
+    match path {
+        Some(path) => Ok(Some(Secret::read_file(path)?)),
+        None => Ok(secret.clone()),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Run the bot (default)
+    Run,
+    /// Manage the chats linked to Pylon accounts
+    Chats {
+        #[command(subcommand)]
+        command: ChatsCommand,
+    },
+    /// Manage the bot admins
+    Admins {
+        #[command(subcommand)]
+        command: AdminsCommand,
+    },
+    /// Check or upgrade the settings file
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommand,
+    },
+    /// Check the Pylon configuration
+    Pylon {
+        #[command(subcommand)]
+        command: PylonCommand,
+    },
+    /// Read the audit log
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChatsCommand {
+    /// List the chats and the Pylon accounts they are linked to
+    List,
+    /// Link a chat to a Pylon account
+    #[command(allow_negative_numbers = true)]
+    Link {
+        chat_id: i64,
+        account_id: String,
+        /// Pylon workspace of the account
+        #[clap(long, default_value = DEFAULT_WORKSPACE)]
+        workspace: String,
+    },
+    /// Remove the link of a chat to its Pylon account
+    #[command(allow_negative_numbers = true)]
+    Unlink { chat_id: i64 },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AdminsCommand {
+    /// Allow a Telegram user to use the admin commands
+    Add { username: String },
+    /// Remove a bot admin
+    Remove { username: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SettingsCommand {
+    /// Check that the settings file can be loaded
+    Validate,
+    /// Rewrite the settings file in the current format, keeping a backup of the previous one
+    Migrate,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PylonCommand {
+    /// Check that the Pylon accounts linked to chats exist
+    CheckAccounts,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditCommand {
+    /// Write the audit entries to stdout
+    Export {
+        #[clap(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// Only export the entries containing this text
+        #[clap(long)]
+        filter: Option<String>,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+/// Checks that a secret token is accepted by Telegram.
+fn parse_secret_token(token: &str) -> Result<Secret, String> {
+    if (1..=256).contains(&token.len())
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(Secret::from(token.to_string()))
+    } else {
+        Err("must be 1 to 256 characters among A-Z, a-z, 0-9, _ and -".to_string())
+    }
 }
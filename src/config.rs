@@ -1,8 +1,23 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
+};
 
+use chrono::NaiveTime;
+use eyre::{WrapErr, bail};
+use regex::{Regex, RegexSet};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
+use crate::secret::Secret;
+
+/// Pylon workspace of the chats that don't set one, using the `--pylon-api-token`.
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+/// Telegram bot of the chats that don't set one, using the `TELOXIDE_TOKEN`.
+pub const DEFAULT_BOT: &str = "default";
+
 pub struct Config {
     settings: RwLock<Settings>,
     settings_path: String,
@@ -10,7 +25,7 @@ pub struct Config {
 
 impl Config {
     pub fn try_new(settings_path: String) -> eyre::Result<Self> {
-        let settings = confy::load_path::<Settings>(settings_path.clone())?;
+        let settings = Settings::load(&settings_path)?;
 
         let settings = Self {
             settings: RwLock::new(settings),
@@ -20,17 +35,23 @@ impl Config {
         Ok(settings)
     }
 
+    pub fn settings_path(&self) -> &str {
+        &self.settings_path
+    }
+
     pub async fn get(&self) -> Settings {
         self.settings.read().await.clone()
     }
 
+    /// Reloads the settings from the file, keeping the current ones if they are invalid.
     pub async fn reload(&self) -> eyre::Result<()> {
-        *self.settings.write().await = confy::load_path::<Settings>(self.settings_path.clone())?;
+        *self.settings.write().await = Settings::load(&self.settings_path)?;
 
         Ok(())
     }
 
     pub fn save(&self, settings: Settings) -> eyre::Result<()> {
+        settings.validate()?;
         confy::store_path(&self.settings_path, settings.clone())?;
 
         Ok(())
@@ -38,7 +59,625 @@ impl Config {
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Settings {
     pub tg_chats_to_pylon_accounts: HashMap<String, String>,
+    #[serde(default)]
+    pub tg_chats_settings: HashMap<String, ChatSettings>,
     pub bot_admins: HashSet<String>,
+    /// Telegram usernames of our team members, who may be granted more rights than customers.
+    #[serde(default)]
+    pub team_members: HashSet<String>,
+    #[serde(default)]
+    pub poller: PollerSettings,
+    #[serde(default)]
+    pub alerts: AlertSettings,
+    #[serde(default)]
+    pub link_check: LinkCheckSettings,
+    /// Pylon workspaces by name, in addition to the default one.
+    #[serde(default)]
+    pub pylon_workspaces: BTreeMap<String, WorkspaceSettings>,
+    /// Telegram bots by name, in addition to the default one.
+    #[serde(default)]
+    pub bots: BTreeMap<String, BotSettings>,
+}
+
+impl Settings {
+    /// Loads and validates the settings file, which must exist.
+    pub fn load(path: &str) -> eyre::Result<Self> {
+        // confy would create a default file instead
+        if !Path::new(path).exists() {
+            bail!("Settings file {path} does not exist");
+        }
+
+        let mut settings = confy::load_path::<Settings>(path)
+            .wrap_err_with(|| format!("Failed to parse settings file {path}"))?;
+
+        settings
+            .validate()
+            .wrap_err_with(|| format!("Invalid settings file {path}"))?;
+
+        for chat_settings in settings.tg_chats_settings.values_mut() {
+            for rule in &mut chat_settings.rules {
+                rule.compile()?;
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Checks the settings that can be parsed but would not work, reporting all the problems found.
+    pub fn validate(&self) -> eyre::Result<()> {
+        let mut problems = Vec::new();
+        let mut chats = HashMap::<i64, Vec<&str>>::new();
+
+        for chat_id in self.tg_chats_to_pylon_accounts.keys() {
+            match chat_id.parse::<i64>() {
+                Ok(id) => chats.entry(id).or_default().push(chat_id),
+                Err(_) => problems.push(format!("chat id '{chat_id}' is not a number")),
+            }
+        }
+
+        for mut keys in chats.into_values().filter(|keys| keys.len() > 1) {
+            keys.sort();
+            problems.push(format!(
+                "chat ids {} are the same chat",
+                keys.iter()
+                    .map(|key| format!("'{key}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        for (name, workspace) in &self.pylon_workspaces {
+            if name.is_empty()
+                || name.len() > 32
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                problems.push(format!(
+                    "workspace name '{name}' should have 1 to 32 letters, digits, '_' or '-'"
+                ));
+            }
+
+            if workspace.token_env.is_some() == workspace.token_file.is_some() {
+                problems.push(format!(
+                    "workspace '{name}' should have either token_env or token_file"
+                ));
+            }
+        }
+
+        for (name, bot) in &self.bots {
+            if bot.token_env.is_some() == bot.token_file.is_some() {
+                problems.push(format!(
+                    "bot '{name}' should have either token_env or token_file"
+                ));
+            }
+        }
+
+        for (chat_id, chat_settings) in &self.tg_chats_settings {
+            if chat_id.parse::<i64>().is_err() {
+                problems.push(format!(
+                    "chat id '{chat_id}' in tg_chats_settings is not a number"
+                ));
+            }
+
+            if let Some(bot) = &chat_settings.bot
+                && bot != DEFAULT_BOT
+                && !self.bots.contains_key(bot)
+            {
+                problems.push(format!(
+                    "bot '{bot}' of chat {chat_id} is not defined in bots"
+                ));
+            }
+
+            if let Some(workspace) = &chat_settings.workspace
+                && workspace != DEFAULT_WORKSPACE
+                && !self.pylon_workspaces.contains_key(workspace)
+            {
+                problems.push(format!(
+                    "workspace '{workspace}' of chat {chat_id} is not defined in pylon_workspaces"
+                ));
+            }
+
+            for topic_id in chat_settings.topics.keys() {
+                if topic_id.parse::<i32>().is_err() {
+                    problems.push(format!(
+                        "topic id '{topic_id}' of chat {chat_id} is not a number"
+                    ));
+                }
+            }
+
+            for rule in &chat_settings.rules {
+                for pattern in &rule.patterns {
+                    if let Err(err) = Regex::new(pattern) {
+                        problems.push(format!(
+                            "pattern '{pattern}' of rule '{}' in chat {chat_id} is invalid: {err}",
+                            rule.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        if self.bot_admins.is_empty() {
+            problems.push("bot_admins is empty".to_string());
+        }
+
+        for username in &self.bot_admins {
+            if username.is_empty() || username.starts_with('@') {
+                problems.push(format!(
+                    "admin '{username}' should be a Telegram username without '@'"
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            problems.sort();
+            bail!("{}", problems.join("; "));
+        }
+
+        Ok(())
+    }
+
+    pub fn chat_settings(&self, chat_id: &str) -> ChatSettings {
+        self.tg_chats_settings
+            .get(chat_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the Pylon workspace of `chat_id`.
+    pub fn workspace(&self, chat_id: &str) -> String {
+        self.tg_chats_settings
+            .get(chat_id)
+            .and_then(|chat_settings| chat_settings.workspace.clone())
+            .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string())
+    }
+
+    /// Returns the Pylon account linked to `chat_id`, if any.
+    pub fn pylon_account(&self, chat_id: &str) -> Option<&str> {
+        self.tg_chats_to_pylon_accounts
+            .get(chat_id)
+            .map(String::as_str)
+            .filter(|account_id| !account_id.is_empty())
+    }
+
+    /// Returns the settings of the forum topic `topic_id` of `chat_id`, if any.
+    pub fn topic_settings(&self, chat_id: &str, topic_id: Option<i32>) -> Option<&TopicSettings> {
+        self.tg_chats_settings
+            .get(chat_id)?
+            .topics
+            .get(&topic_id?.to_string())
+    }
+
+    /// Returns the Pylon account linked to the forum topic `topic_id` of `chat_id`, or to the chat
+    /// if the topic has none.
+    pub fn topic_account(&self, chat_id: &str, topic_id: Option<i32>) -> Option<&str> {
+        self.topic_settings(chat_id, topic_id)
+            .and_then(|topic| topic.account_id.as_deref())
+            .filter(|account_id| !account_id.is_empty())
+            .or_else(|| self.pylon_account(chat_id))
+    }
+
+    /// Returns the Telegram bot of `chat_id`.
+    pub fn bot(&self, chat_id: &str) -> String {
+        self.tg_chats_settings
+            .get(chat_id)
+            .and_then(|chat_settings| chat_settings.bot.clone())
+            .unwrap_or_else(|| DEFAULT_BOT.to_string())
+    }
+
+    /// Returns the chats of the Telegram bot `bot`, with their Pylon account if linked.
+    pub fn bot_chats(&self, bot: &str) -> Vec<(&String, &String)> {
+        let mut chats = self
+            .tg_chats_to_pylon_accounts
+            .iter()
+            .filter(|(chat_id, _)| self.bot(chat_id) == bot)
+            .collect::<Vec<_>>();
+        chats.sort();
+
+        chats
+    }
+
+    /// Adds `chat_id`, not linked to a Pylon account yet, to the chats of `bot`.
+    pub fn add_chat(&mut self, chat_id: &str, bot: &str) {
+        self.tg_chats_to_pylon_accounts
+            .insert(chat_id.to_string(), String::default());
+
+        let bot = Some(bot.to_string()).filter(|name| name != DEFAULT_BOT);
+
+        if bot.is_some() || self.tg_chats_settings.contains_key(chat_id) {
+            self.tg_chats_settings
+                .entry(chat_id.to_string())
+                .or_default()
+                .bot = bot;
+        }
+    }
+
+    /// Links `chat_id` to the Pylon account `account_id` of `workspace`.
+    pub fn link(&mut self, chat_id: &str, workspace: &str, account_id: &str) {
+        self.tg_chats_to_pylon_accounts
+            .insert(chat_id.to_string(), account_id.to_string());
+
+        let workspace = Some(workspace.to_string()).filter(|name| name != DEFAULT_WORKSPACE);
+
+        if workspace.is_some() || self.tg_chats_settings.contains_key(chat_id) {
+            self.tg_chats_settings
+                .entry(chat_id.to_string())
+                .or_default()
+                .workspace = workspace;
+        }
+    }
+
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.bot_admins.contains(username)
+    }
+
+    /// Whether `username` belongs to the team. Bot admins are team members.
+    pub fn is_team_member(&self, username: &str) -> bool {
+        self.team_members.contains(username) || self.is_admin(username)
+    }
+}
+
+/// Polling of Pylon for updates of the issues created by the bot, for deployments that can't
+/// receive Pylon webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollerSettings {
+    /// Off by default, and ignored when Pylon webhooks are received.
+    pub enabled: bool,
+    /// Seconds between two polls.
+    pub interval_secs: u64,
+    /// Maximum number of issues checked per poll. Issues are checked in turns when there are more.
+    pub batch_size: usize,
+    /// Issues created more days ago are not polled anymore, nor closed issues.
+    pub max_age_days: i64,
+    /// Time range, in UTC, when Pylon is not polled to avoid notifying users at night.
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for PollerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 300,
+            batch_size: 50,
+            max_age_days: 30,
+            quiet_hours: None,
+        }
+    }
+}
+
+/// Forwarding of errors to admin chats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertSettings {
+    /// Chats errors are sent to, none if empty.
+    pub chat_ids: Vec<i64>,
+    /// Minutes during which an error is not sent again.
+    pub dedup_minutes: u64,
+    /// Maximum number of errors sent per hour, the others only appear in the digest.
+    pub max_per_hour: usize,
+    /// Time, in UTC, of the daily digest of errors. No digest is sent if not set.
+    pub digest_time: Option<NaiveTime>,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            chat_ids: Vec::new(),
+            dedup_minutes: 60,
+            max_per_hour: 20,
+            digest_time: Some(NaiveTime::from_hms_opt(9, 0, 0).unwrap()),
+        }
+    }
+}
+
+/// Checks that the linked chats and Pylon accounts still exist. Broken links are sent to the
+/// alert chats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkCheckSettings {
+    pub enabled: bool,
+    /// Hours between two checks, the first one being at startup.
+    pub interval_hours: u64,
+}
+
+impl Default for LinkCheckSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // The range spans midnight
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// Group of users allowed to perform an action.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Everyone,
+    #[default]
+    Team,
+    Admins,
+}
+
+impl Role {
+    pub fn allows(&self, username: Option<&str>, settings: &Settings) -> bool {
+        match self {
+            Role::Everyone => true,
+            Role::Team => username.is_some_and(|username| settings.is_team_member(username)),
+            Role::Admins => username.is_some_and(|username| settings.is_admin(username)),
+        }
+    }
+}
+
+/// Pylon workspace, whose API token is read from an environment variable or a file rather than
+/// written in the settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceSettings {
+    /// URL of the Pylon API, `https://api.usepylon.com` if not set.
+    pub base_url: Option<String>,
+    /// Environment variable holding the API token.
+    pub token_env: Option<String>,
+    /// File holding the API token.
+    pub token_file: Option<PathBuf>,
+}
+
+impl WorkspaceSettings {
+    pub fn token(&self) -> eyre::Result<Secret> {
+        read_token(self.token_env.as_deref(), self.token_file.as_deref())
+    }
+}
+
+/// Telegram bot, whose token is read from an environment variable or a file rather than written
+/// in the settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotSettings {
+    /// Expected Telegram username of the bot, optional since Telegram gives it for the token. A
+    /// different one is only reported at startup.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Environment variable holding the bot token.
+    pub token_env: Option<String>,
+    /// File holding the bot token.
+    pub token_file: Option<PathBuf>,
+}
+
+impl BotSettings {
+    pub fn token(&self) -> eyre::Result<Secret> {
+        read_token(self.token_env.as_deref(), self.token_file.as_deref())
+    }
+}
+
+fn read_token(token_env: Option<&str>, token_file: Option<&Path>) -> eyre::Result<Secret> {
+    match (token_env, token_file) {
+        (Some(name), _) => Ok(env::var(name)
+            .wrap_err_with(|| format!("Failed to read the token from ${name}"))?
+            .trim()
+            .parse()?),
+        (None, Some(path)) => Secret::read_file(path),
+        (None, None) => bail!("No token_env or token_file"),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatSettings {
+    /// Pylon workspace of the account linked to the chat, the default one if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Telegram bot added to the chat, the default one if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot: Option<String>,
+    /// Values applied to every issue created from the chat.
+    #[serde(default)]
+    pub defaults: IssueDefaults,
+    /// Creating issues by reacting to messages.
+    #[serde(default)]
+    pub reaction: ReactionTrigger,
+    /// Rules creating issues from the messages they match.
+    #[serde(default)]
+    pub rules: Vec<IssueRule>,
+    /// Users allowed to close, reopen and comment issues from the chat.
+    #[serde(default)]
+    pub issue_actions: Role,
+    /// Whether state changes of the issues created from the chat are announced in the chat.
+    #[serde(default = "default_true")]
+    pub notify_state_changes: bool,
+    /// Whether issue confirmations are sent privately to the requester rather than to the chat.
+    #[serde(default)]
+    pub quiet: bool,
+    /// Whether `/issue` commands replying to a message are deleted once handled, which requires
+    /// the bot to be an admin of the chat.
+    #[serde(default)]
+    pub delete_commands: bool,
+    /// Settings of the forum topics of the chat, by topic id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub topics: BTreeMap<String, TopicSettings>,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            workspace: None,
+            bot: None,
+            defaults: IssueDefaults::default(),
+            reaction: ReactionTrigger::default(),
+            rules: Vec::new(),
+            issue_actions: Role::default(),
+            notify_state_changes: true,
+            quiet: false,
+            delete_commands: false,
+            topics: BTreeMap::new(),
+        }
+    }
+}
+
+/// Forum topic whose issues go to another Pylon account or get more tags than the rest of the
+/// chat.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopicSettings {
+    /// Pylon account of the issues created from the topic, in the chat's workspace.
+    pub account_id: Option<String>,
+    /// Tags added to the chat's default tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Rule matching the messages of a chat that should become issues.
+///
+/// A message matches if its text contains one of the keywords or matches one of the patterns,
+/// is at least `min_length` characters long and was sent by one of `senders`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IssueRule {
+    pub name: String,
+    /// Case insensitive keywords.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Regular expressions.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub senders: Senders,
+    #[serde(default)]
+    pub min_length: usize,
+    #[serde(default)]
+    pub action: RuleAction,
+    /// `patterns` compiled once, when the settings are loaded.
+    #[serde(skip)]
+    regexes: RegexSet,
+}
+
+impl IssueRule {
+    /// Compiles the patterns of the rule, before matching messages.
+    pub fn compile(&mut self) -> Result<(), regex::Error> {
+        self.regexes = RegexSet::new(&self.patterns)?;
+
+        Ok(())
+    }
+
+    pub fn matches(&self, text: &str, username: Option<&str>, settings: &Settings) -> bool {
+        if text.chars().count() < self.min_length || !self.senders.includes(username, settings) {
+            return false;
+        }
+
+        let lowercase_text = text.to_lowercase();
+
+        self.keywords
+            .iter()
+            .any(|keyword| !keyword.is_empty() && lowercase_text.contains(&keyword.to_lowercase()))
+            || self.regexes.is_match(text)
+    }
+}
+
+/// Senders of the messages a rule applies to.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Senders {
+    #[default]
+    Everyone,
+    /// Users who are not team members.
+    Customers,
+    Team,
+}
+
+impl Senders {
+    pub fn includes(&self, username: Option<&str>, settings: &Settings) -> bool {
+        let is_team_member = username.is_some_and(|username| settings.is_team_member(username));
+
+        match self {
+            Senders::Everyone => true,
+            Senders::Customers => !is_team_member,
+            Senders::Team => is_team_member,
+        }
+    }
+}
+
+/// What to do with a message matching a rule.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Create the issue right away.
+    Create,
+    /// Ask the team members in the chat to confirm the issue creation.
+    #[default]
+    Confirm,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReactionTrigger {
+    /// Emoji creating an issue from the message it is added to. Disabled when unset.
+    pub emoji: Option<String>,
+    /// Users whose reactions create issues.
+    #[serde(default)]
+    pub allowed: Role,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IssueDefaults {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub priority: Option<String>,
+    pub assignee_id: Option<String>,
+    pub team_id: Option<String>,
+    /// Custom field values, by custom field slug.
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, String>,
+    pub title_prefix: Option<String>,
+    /// Template of the issue body, where `{body}`, `{username}`, `{chat}` and `{topic}` are
+    /// replaced by the message text, the requester's username, the chat title and the forum topic
+    /// name. Without template, the topic is added after the message text.
+    pub body_template: Option<String>,
+}
+
+impl IssueDefaults {
+    pub fn title(&self, title: &str) -> String {
+        format!(
+            "{}{title}",
+            self.title_prefix.as_deref().unwrap_or_default()
+        )
+    }
+
+    pub fn body(&self, body: &str, username: &str, chat: &str, topic: &str) -> String {
+        match &self.body_template {
+            Some(template) => template
+                .replace("{username}", username)
+                .replace("{chat}", chat)
+                .replace("{topic}", topic)
+                .replace("{body}", body),
+            None if !topic.is_empty() => format!("{body}\n\nTopic: {topic}"),
+            None => body.to_string(),
+        }
+    }
 }
@@ -0,0 +1,34 @@
+use teloxide::{Bot, prelude::Requester, types::ChatId};
+
+use crate::storage::Storage;
+
+/// Number of entries shown by `/audit`.
+const AUDIT_ENTRIES: usize = 20;
+
+/// Handles `/audit [filter]`, showing the latest entries of the audit log matching `filter`.
+pub async fn show_audit(
+    bot: &Bot,
+    chat_id: ChatId,
+    filter: &str,
+    storage: &Storage,
+) -> eyre::Result<()> {
+    let filter = filter.trim();
+    let entries = storage.audit_log().entries().await?;
+    let latest = entries
+        .iter()
+        .rev()
+        .filter(|entry| filter.is_empty() || entry.matches(filter))
+        .take(AUDIT_ENTRIES)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    let text = if latest.is_empty() {
+        "No matching audit entries".to_string()
+    } else {
+        format!("🗒 Latest audit entries:\n\n{}", latest.join("\n"))
+    };
+
+    bot.send_message(chat_id, text).await?;
+
+    Ok(())
+}
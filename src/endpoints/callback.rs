@@ -0,0 +1,207 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    config::{ChatSettings, Role},
+    endpoints::chat_defaults::DefaultField,
+};
+
+/// Data attached to the inline keyboard buttons sent by the bot, encoded as `action:arg[:arg]`
+/// to fit in the 64 bytes allowed by Telegram.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallbackData {
+    /// Link a chat to a Pylon account, of the chat's workspace if not set.
+    Link {
+        chat_id: String,
+        workspace: Option<String>,
+    },
+    /// Show the issue defaults of a chat.
+    Defaults { chat_id: String },
+    /// Edit one of the issue defaults of a chat.
+    EditDefault {
+        chat_id: String,
+        field: DefaultField,
+    },
+    /// Show the rules of a chat.
+    Rules { chat_id: String },
+    /// Add a rule to a chat.
+    AddRule { chat_id: String },
+    /// Delete a rule of a chat.
+    DeleteRule { chat_id: String, index: usize },
+    /// Create an issue from a message of the chat the button was sent to.
+    CreateIssue { message_id: i32 },
+    /// Delete the message the button belongs to.
+    Dismiss,
+    /// Show a page of the open issues of the chat the button was sent to.
+    Issues { page: usize },
+    /// Close an issue of the chat the button was sent to.
+    CloseIssue { number: u64 },
+    /// Comment an issue of the chat the button was sent to.
+    CommentIssue { number: u64 },
+    /// Create an issue in a chat from the messages sent privately to the bot.
+    Forward { chat_id: i64 },
+}
+
+impl CallbackData {
+    pub fn parse(data: &str) -> Option<Self> {
+        let mut parts = data.split(':');
+
+        match (parts.next()?, parts.next(), parts.next()) {
+            ("link", Some(chat_id), workspace) => Some(CallbackData::Link {
+                chat_id: chat_id.to_string(),
+                workspace: workspace.map(str::to_string),
+            }),
+            ("defaults", Some(chat_id), None) => Some(CallbackData::Defaults {
+                chat_id: chat_id.to_string(),
+            }),
+            ("default", Some(chat_id), Some(field)) => Some(CallbackData::EditDefault {
+                chat_id: chat_id.to_string(),
+                field: field.parse().ok()?,
+            }),
+            ("rules", Some(chat_id), None) => Some(CallbackData::Rules {
+                chat_id: chat_id.to_string(),
+            }),
+            ("addrule", Some(chat_id), None) => Some(CallbackData::AddRule {
+                chat_id: chat_id.to_string(),
+            }),
+            ("delrule", Some(chat_id), Some(index)) => Some(CallbackData::DeleteRule {
+                chat_id: chat_id.to_string(),
+                index: index.parse().ok()?,
+            }),
+            ("issue", Some(message_id), None) => Some(CallbackData::CreateIssue {
+                message_id: message_id.parse().ok()?,
+            }),
+            ("dismiss", None, None) => Some(CallbackData::Dismiss),
+            ("issues", Some(page), None) => Some(CallbackData::Issues {
+                page: page.parse().ok()?,
+            }),
+            ("close", Some(number), None) => Some(CallbackData::CloseIssue {
+                number: number.parse().ok()?,
+            }),
+            ("comment", Some(number), None) => Some(CallbackData::CommentIssue {
+                number: number.parse().ok()?,
+            }),
+            ("forward", Some(chat_id), None) => Some(CallbackData::Forward {
+                chat_id: chat_id.parse().ok()?,
+            }),
+            // Buttons sent before callback data had an action only carried the chat to link
+            (chat_id, None, None) => Some(CallbackData::Link {
+                chat_id: chat_id.to_string(),
+                workspace: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Users allowed to press the button, given the settings of the chat it was sent to.
+    pub fn allowed(&self, chat_settings: &ChatSettings) -> Role {
+        match self {
+            CallbackData::Issues { .. } | CallbackData::Forward { .. } => Role::Everyone,
+            CallbackData::CloseIssue { .. } | CallbackData::CommentIssue { .. } => {
+                chat_settings.issue_actions
+            }
+            CallbackData::CreateIssue { .. } | CallbackData::Dismiss => Role::Team,
+            _ => Role::Admins,
+        }
+    }
+}
+
+impl Display for CallbackData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackData::Link {
+                chat_id,
+                workspace: None,
+            } => write!(f, "link:{chat_id}"),
+            CallbackData::Link {
+                chat_id,
+                workspace: Some(workspace),
+            } => write!(f, "link:{chat_id}:{workspace}"),
+            CallbackData::Defaults { chat_id } => write!(f, "defaults:{chat_id}"),
+            CallbackData::EditDefault { chat_id, field } => {
+                write!(f, "default:{chat_id}:{}", field.as_str())
+            }
+            CallbackData::Rules { chat_id } => write!(f, "rules:{chat_id}"),
+            CallbackData::AddRule { chat_id } => write!(f, "addrule:{chat_id}"),
+            CallbackData::DeleteRule { chat_id, index } => write!(f, "delrule:{chat_id}:{index}"),
+            CallbackData::CreateIssue { message_id } => write!(f, "issue:{message_id}"),
+            CallbackData::Dismiss => write!(f, "dismiss"),
+            CallbackData::Issues { page } => write!(f, "issues:{page}"),
+            CallbackData::CloseIssue { number } => write!(f, "close:{number}"),
+            CallbackData::CommentIssue { number } => write!(f, "comment:{number}"),
+            CallbackData::Forward { chat_id } => write!(f, "forward:{chat_id}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallbackData;
+    use crate::endpoints::chat_defaults::DefaultField;
+
+    /// Longest chat id, as a string, of a supergroup.
+    const CHAT_ID: &str = "-1009876543210";
+
+    #[test]
+    fn test_callback_data_round_trip() {
+        let mut all_data = vec![
+            CallbackData::Link {
+                chat_id: CHAT_ID.to_string(),
+                workspace: None,
+            },
+            CallbackData::Link {
+                chat_id: CHAT_ID.to_string(),
+                workspace: Some("a".repeat(32)),
+            },
+            CallbackData::Defaults {
+                chat_id: CHAT_ID.to_string(),
+            },
+            CallbackData::Rules {
+                chat_id: CHAT_ID.to_string(),
+            },
+            CallbackData::AddRule {
+                chat_id: CHAT_ID.to_string(),
+            },
+            CallbackData::DeleteRule {
+                chat_id: CHAT_ID.to_string(),
+                index: 12,
+            },
+            CallbackData::CreateIssue {
+                message_id: i32::MAX,
+            },
+            CallbackData::Dismiss,
+            CallbackData::Issues { page: 3 },
+            CallbackData::CloseIssue { number: 4821 },
+            CallbackData::CommentIssue { number: 4821 },
+            CallbackData::Forward {
+                chat_id: CHAT_ID.parse().unwrap(),
+            },
+        ];
+        all_data.extend(DefaultField::ALL.map(|field| CallbackData::EditDefault {
+            chat_id: CHAT_ID.to_string(),
+            field,
+        }));
+
+        for data in all_data {
+            let encoded = data.to_string();
+
+            assert!(encoded.len() <= 64, "{encoded} is too long");
+            assert_eq!(CallbackData::parse(&encoded), Some(data));
+        }
+    }
+
+    #[test]
+    fn test_parse_legacy_and_invalid_callback_data() {
+        assert_eq!(
+            CallbackData::parse(CHAT_ID),
+            Some(CallbackData::Link {
+                chat_id: CHAT_ID.to_string(),
+                workspace: None
+            })
+        );
+
+        assert_eq!(CallbackData::parse("close:abc"), None);
+        assert_eq!(CallbackData::parse("default:-100123:unknown"), None);
+        assert_eq!(CallbackData::parse("dismiss:1"), None);
+        assert_eq!(CallbackData::parse("forward:-100123:1"), None);
+    }
+}
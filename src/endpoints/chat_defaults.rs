@@ -0,0 +1,371 @@
+use std::{str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    Bot,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, UserId},
+};
+use tracing::{info, warn};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    config::{Config, IssueDefaults, Settings},
+    endpoints::{
+        LinkToPylonAccountDialogue, State, callback::CallbackData, is_bot_admin, select_linked_chat,
+    },
+    pylon::{PRIORITIES, PylonWorkspaces},
+    storage::Storage,
+};
+
+/// Value typed to clear an issue default.
+const CLEAR: &str = "-";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DefaultField {
+    Tags,
+    Priority,
+    Assignee,
+    Team,
+    CustomField,
+    TitlePrefix,
+    BodyTemplate,
+}
+
+impl DefaultField {
+    pub const ALL: [DefaultField; 7] = [
+        DefaultField::Tags,
+        DefaultField::Priority,
+        DefaultField::Assignee,
+        DefaultField::Team,
+        DefaultField::CustomField,
+        DefaultField::TitlePrefix,
+        DefaultField::BodyTemplate,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DefaultField::Tags => "tags",
+            DefaultField::Priority => "priority",
+            DefaultField::Assignee => "assignee",
+            DefaultField::Team => "team",
+            DefaultField::CustomField => "custom_field",
+            DefaultField::TitlePrefix => "title_prefix",
+            DefaultField::BodyTemplate => "body_template",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            DefaultField::Tags => "Tags",
+            DefaultField::Priority => "Priority",
+            DefaultField::Assignee => "Assignee",
+            DefaultField::Team => "Team",
+            DefaultField::CustomField => "Custom field",
+            DefaultField::TitlePrefix => "Title prefix",
+            DefaultField::BodyTemplate => "Body template",
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        let prompt = match self {
+            DefaultField::Tags => "Please enter the Pylon tags, separated by spaces:".to_string(),
+            DefaultField::Priority => format!(
+                "Please enter the priority, one of: {}",
+                PRIORITIES.join(", ")
+            ),
+            DefaultField::Assignee => {
+                "Please enter the email or name of the Pylon user to assign issues to:".to_string()
+            }
+            DefaultField::Team => "Please enter the name of the Pylon team:".to_string(),
+            DefaultField::CustomField => {
+                "Please enter the custom field as slug=value (slug= to remove it):".to_string()
+            }
+            DefaultField::TitlePrefix => "Please enter the title prefix:".to_string(),
+            DefaultField::BodyTemplate => {
+                "Please enter the body template. {body}, {username}, {chat} and {topic} are \
+                 replaced by the message text, its author, the chat title and the forum topic:"
+                    .to_string()
+            }
+        };
+
+        format!("{prompt}\n\nSend {CLEAR} to clear it.")
+    }
+}
+
+impl FromStr for DefaultField {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DefaultField::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| eyre::eyre!("Unknown issue default: {s}"))
+    }
+}
+
+/// Lists the linked chats whose issue defaults can be edited.
+pub async fn chat_defaults(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    settings: Settings,
+) -> eyre::Result<()> {
+    select_linked_chat(bot, chat_id, bot_name, &settings, |chat_id| {
+        CallbackData::Defaults { chat_id }
+    })
+    .await
+}
+
+/// Sends the issue defaults of `tg_chat_id`, with a button to edit each of them.
+pub async fn show_chat_defaults(
+    bot: &Bot,
+    chat_id: ChatId,
+    tg_chat_id: &str,
+    settings: &Settings,
+) -> eyre::Result<()> {
+    let chat = bot.get_chat(tg_chat_id.to_string()).await?;
+    let defaults = settings.chat_settings(tg_chat_id).defaults;
+
+    let keyboard = DefaultField::ALL
+        .chunks(2)
+        .map(|fields| {
+            fields
+                .iter()
+                .map(|field| {
+                    InlineKeyboardButton::callback(
+                        field.label(),
+                        CallbackData::EditDefault {
+                            chat_id: tg_chat_id.to_string(),
+                            field: *field,
+                        }
+                        .to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Issue defaults for {}:\n\n{}",
+            chat.title().unwrap_or_default(),
+            describe_defaults(&defaults)
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::new(keyboard))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_chat_default_input(
+    bot: Bot,
+    message: Message,
+    dialogue: LinkToPylonAccountDialogue,
+    (_, chat_id, field): (UserId, String, DefaultField),
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let Some(value) = message.text() else {
+        return Ok(());
+    };
+
+    // Reset dialogue to start
+    dialogue.update(State::Start).await?;
+
+    let mut settings = config.get().await;
+
+    // The admin could have lost their rights since they started the dialogue
+    if !is_bot_admin(message.from.as_ref(), &settings) {
+        warn!("Unauthorized answer to the issue default prompt");
+        return Ok(());
+    }
+
+    let workspace = settings.workspace(&chat_id);
+    let chat_settings = settings
+        .tg_chats_settings
+        .entry(chat_id.clone())
+        .or_default();
+
+    if let Err(err) = set_default(
+        &mut chat_settings.defaults,
+        field,
+        value,
+        &workspaces,
+        &workspace,
+    )
+    .await
+    {
+        bot.send_message(message.chat.id, format!("⚠️ {err}"))
+            .await?;
+        return Ok(());
+    }
+
+    config.save(settings.clone())?;
+
+    info!(
+        "Issue default '{}' of chat '{chat_id}' updated",
+        field.as_str()
+    );
+
+    storage
+        .audit_log()
+        .record(
+            AuditEntry::new(
+                message.from.as_ref().map(|user| user.id.0),
+                AuditAction::SettingsChange,
+                &chat_id,
+            )
+            .details(format!(
+                "default {} set to '{}'",
+                field.as_str(),
+                value.trim()
+            )),
+        )
+        .await?;
+
+    show_chat_defaults(&bot, message.chat.id, &chat_id, &settings).await
+}
+
+async fn set_default(
+    defaults: &mut IssueDefaults,
+    field: DefaultField,
+    value: &str,
+    workspaces: &PylonWorkspaces,
+    workspace: &str,
+) -> eyre::Result<()> {
+    let clear = value.trim() == CLEAR;
+
+    match field {
+        DefaultField::Tags if clear => defaults.tags.clear(),
+        DefaultField::Tags => {
+            let known_tags = workspaces.get(workspace)?.get_tags().await?;
+            let mut tags = Vec::new();
+
+            for tag in value.split([' ', ',']).filter(|t| !t.is_empty()) {
+                let tag = tag.trim_start_matches('+');
+                let known = known_tags
+                    .iter()
+                    .find(|t| t.value.eq_ignore_ascii_case(tag))
+                    .ok_or_else(|| eyre::eyre!("Unknown tag {tag}"))?;
+
+                tags.push(known.value.clone());
+            }
+
+            defaults.tags = tags;
+        }
+        DefaultField::Priority if clear => defaults.priority = None,
+        DefaultField::Priority => {
+            let priority = value.trim().trim_start_matches('#').to_lowercase();
+
+            if !PRIORITIES.contains(&priority.as_str()) {
+                eyre::bail!("Unknown priority {priority}");
+            }
+
+            defaults.priority = Some(priority);
+        }
+        DefaultField::Assignee if clear => defaults.assignee_id = None,
+        DefaultField::Assignee => {
+            let handle = value.trim().trim_start_matches('@');
+            let users = workspaces.get(workspace)?.get_users().await?;
+            let matching = users
+                .iter()
+                .filter(|u| {
+                    u.matches_handle(handle)
+                        || u.email.as_deref().is_some_and(|email| email == handle)
+                })
+                .collect::<Vec<_>>();
+
+            match matching.as_slice() {
+                [user] => defaults.assignee_id = Some(user.id.clone()),
+                [] => eyre::bail!("Unknown Pylon user {handle}"),
+                _ => eyre::bail!("{handle} matches several Pylon users"),
+            }
+        }
+        DefaultField::Team if clear => defaults.team_id = None,
+        DefaultField::Team => {
+            let name = value.trim();
+            let teams = workspaces.get(workspace)?.get_teams().await?;
+            let team = teams
+                .iter()
+                .find(|t| {
+                    t.id == name
+                        || t.name
+                            .as_deref()
+                            .is_some_and(|n| n.eq_ignore_ascii_case(name))
+                })
+                .ok_or_else(|| eyre::eyre!("Unknown Pylon team {name}"))?;
+
+            defaults.team_id = Some(team.id.clone());
+        }
+        DefaultField::CustomField if clear => defaults.custom_fields.clear(),
+        DefaultField::CustomField => {
+            let (slug, field_value) = value
+                .split_once('=')
+                .ok_or_else(|| eyre::eyre!("Expected slug=value"))?;
+            let (slug, field_value) = (slug.trim(), field_value.trim());
+
+            if field_value.is_empty() {
+                defaults.custom_fields.remove(slug);
+            } else {
+                defaults
+                    .custom_fields
+                    .insert(slug.to_string(), field_value.to_string());
+            }
+        }
+        DefaultField::TitlePrefix if clear => defaults.title_prefix = None,
+        DefaultField::TitlePrefix => defaults.title_prefix = Some(value.to_string()),
+        DefaultField::BodyTemplate if clear => defaults.body_template = None,
+        DefaultField::BodyTemplate => defaults.body_template = Some(value.to_string()),
+    }
+
+    Ok(())
+}
+
+fn describe_defaults(defaults: &IssueDefaults) -> String {
+    let none = || "-".to_string();
+
+    [
+        (DefaultField::Tags, defaults.tags.join(", ")),
+        (
+            DefaultField::Priority,
+            defaults.priority.clone().unwrap_or_else(none),
+        ),
+        (
+            DefaultField::Assignee,
+            defaults.assignee_id.clone().unwrap_or_else(none),
+        ),
+        (
+            DefaultField::Team,
+            defaults.team_id.clone().unwrap_or_else(none),
+        ),
+        (
+            DefaultField::CustomField,
+            defaults
+                .custom_fields
+                .iter()
+                .map(|(slug, value)| format!("{slug}={value}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        (
+            DefaultField::TitlePrefix,
+            defaults.title_prefix.clone().unwrap_or_else(none),
+        ),
+        (
+            DefaultField::BodyTemplate,
+            defaults.body_template.clone().unwrap_or_else(none),
+        ),
+    ]
+    .into_iter()
+    .map(|(field, value)| {
+        let value = if value.is_empty() { none() } else { value };
+        format!("{}: {value}", field.label())
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
@@ -0,0 +1,573 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use eyre::WrapErr;
+use teloxide::{
+    Bot,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{
+        ChatAction, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageOrigin,
+        ParseMode, User, UserId,
+    },
+    utils::command::BotCommands,
+};
+use tracing::{debug, info};
+
+use crate::{
+    alerts::AlertContext,
+    bots::BotIdentity,
+    config::{Config, Settings},
+    endpoints::{
+        IssueSource, callback::CallbackData, count_command, create_issue, display_name,
+        escape_markdown_v2, issue_args::IssueArgs, message_attachment, message_text, split_title,
+        title_from_message,
+    },
+    pylon::PylonWorkspaces,
+    storage::Storage,
+};
+
+/// Number of messages an issue can be created from privately.
+const MAX_FORWARDED_MESSAGES: usize = 20;
+
+/// Time after which the messages of a user who neither sent `/issue` nor `/cancel` are dropped.
+const FORWARDED_MESSAGES_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase")]
+pub enum PrivateCommand {
+    /// Explain how to create an issue from private messages.
+    Start,
+
+    /// Create an issue from the messages sent or forwarded to the bot: `/issue [title]`.
+    #[command()]
+    Issue(String),
+
+    /// Discard the messages sent or forwarded to the bot.
+    #[command()]
+    Cancel,
+}
+
+/// Messages sent or forwarded privately to the bot, by user, until an issue is created from them.
+#[derive(Default)]
+pub struct ForwardedMessages {
+    users: Mutex<HashMap<UserId, Bundle>>,
+}
+
+struct Bundle {
+    messages: Vec<Message>,
+    /// Arguments of `/issue`, kept while the user chooses the chat of the issue.
+    args: String,
+    updated_at: Instant,
+}
+
+impl ForwardedMessages {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Locks the messages, dropping the ones that have not been updated for too long.
+    fn users(&self) -> MutexGuard<'_, HashMap<UserId, Bundle>> {
+        let mut users = self.users.lock().unwrap();
+        users.retain(|_, bundle| bundle.updated_at.elapsed() < FORWARDED_MESSAGES_LIFETIME);
+
+        users
+    }
+
+    /// Adds `message` to the messages of `user_id` and returns their number, or none if there are
+    /// too many already.
+    fn push(&self, user_id: UserId, message: Message) -> Option<usize> {
+        let mut users = self.users();
+        let bundle = users.entry(user_id).or_insert_with(|| Bundle {
+            messages: Vec::new(),
+            args: String::new(),
+            updated_at: Instant::now(),
+        });
+
+        if bundle.messages.len() == MAX_FORWARDED_MESSAGES {
+            return None;
+        }
+
+        bundle.messages.push(message);
+        bundle.updated_at = Instant::now();
+
+        Some(bundle.messages.len())
+    }
+
+    fn messages(&self, user_id: UserId) -> Vec<Message> {
+        self.users()
+            .get(&user_id)
+            .map(|bundle| bundle.messages.clone())
+            .unwrap_or_default()
+    }
+
+    fn args(&self, user_id: UserId) -> String {
+        self.users()
+            .get(&user_id)
+            .map(|bundle| bundle.args.clone())
+            .unwrap_or_default()
+    }
+
+    fn set_args(&self, user_id: UserId, args: String) {
+        if let Some(bundle) = self.users().get_mut(&user_id) {
+            bundle.args = args;
+            bundle.updated_at = Instant::now();
+        }
+    }
+
+    fn clear(&self, user_id: UserId) {
+        self.users().remove(&user_id);
+    }
+}
+
+/// Keeps the messages sent or forwarded privately, to create an issue from them with `/issue`.
+pub async fn handle_private_message(
+    bot: Bot,
+    message: Message,
+    forwarded: Arc<ForwardedMessages>,
+) -> eyre::Result<()> {
+    let Some(user) = &message.from else {
+        return Ok(());
+    };
+
+    // Unknown commands and messages without content, such as stickers, are ignored
+    if message_text(&message).is_some_and(|text| text.starts_with('/'))
+        || (message_text(&message).is_none() && message_attachment(&message).is_none())
+    {
+        return Ok(());
+    }
+
+    let text = match forwarded.push(user.id, message.clone()) {
+        Some(count) => format!(
+            "📎 {count} message(s) ready. Send /issue <title> to create an issue from them, or \
+             /cancel to discard them"
+        ),
+        None => format!(
+            "⚠️ An issue can be created from up to {MAX_FORWARDED_MESSAGES} messages, please \
+             send /issue <title> now"
+        ),
+    };
+
+    bot.send_message(message.chat.id, text).await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn process_private_command(
+    bot: Bot,
+    message: Message,
+    cmd: PrivateCommand,
+    identity: BotIdentity,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    forwarded: Arc<ForwardedMessages>,
+) -> eyre::Result<()> {
+    count_command(&message);
+    let context = AlertContext::for_message(&message);
+
+    async move {
+        let Some(user) = &message.from else {
+            return Ok(());
+        };
+
+        match cmd {
+            PrivateCommand::Start => {
+                bot.send_message(
+                    message.chat.id,
+                    "Send or forward me the messages to report, then send /issue <title> to \
+                     create a Pylon issue from them in one of your chats.",
+                )
+                .await?;
+            }
+            PrivateCommand::Cancel => {
+                forwarded.clear(user.id);
+
+                bot.send_message(message.chat.id, "Messages discarded")
+                    .await?;
+            }
+            PrivateCommand::Issue(args) => {
+                if forwarded.messages(user.id).is_empty() {
+                    bot.send_message(
+                        message.chat.id,
+                        "⚠️ Please first send or forward me the messages to create an issue from",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+
+                bot.send_chat_action(message.chat.id, ChatAction::Typing)
+                    .await?;
+
+                let settings = config.get().await;
+                let chats = member_chats(&bot, user.id, &identity.name, &settings).await;
+
+                match chats.as_slice() {
+                    [] => {
+                        bot.send_message(
+                            message.chat.id,
+                            "⚠️ You are not a member of any chat linked to Pylon",
+                        )
+                        .await?;
+                    }
+                    [(chat_id, chat_title)] => {
+                        forwarded.set_args(user.id, args);
+
+                        create_forwarded_issue(
+                            &bot,
+                            user,
+                            *chat_id,
+                            chat_title,
+                            &workspaces,
+                            &settings,
+                            &storage,
+                            &forwarded,
+                        )
+                        .await?;
+                    }
+                    _ => {
+                        forwarded.set_args(user.id, args);
+
+                        let keyboard =
+                            InlineKeyboardMarkup::new(chats.iter().map(|(chat_id, chat_title)| {
+                                [InlineKeyboardButton::callback(
+                                    chat_title.clone(),
+                                    CallbackData::Forward { chat_id: chat_id.0 }.to_string(),
+                                )]
+                            }));
+
+                        bot.send_message(message.chat.id, "Please select the chat of the issue:")
+                            .reply_markup(keyboard)
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        eyre::Ok(())
+    }
+    .await
+    .wrap_err(context)
+}
+
+/// Handles the choice of the chat of an issue created from private messages.
+pub async fn forward_to_chat(
+    bot: &Bot,
+    user: &User,
+    chat_id: ChatId,
+    workspaces: &PylonWorkspaces,
+    settings: &Settings,
+    storage: &Storage,
+    forwarded: &ForwardedMessages,
+) -> eyre::Result<()> {
+    // The buttons only list the chats of the user, but their data could be forged
+    if settings.pylon_account(&chat_id.to_string()).is_none()
+        || !is_chat_member(bot, chat_id, user.id).await
+    {
+        bot.send_message(user.id, "⚠️ You are not a member of this chat")
+            .await?;
+        return Ok(());
+    }
+
+    let chat_title = chat_title(bot, chat_id).await;
+
+    create_forwarded_issue(
+        bot,
+        user,
+        chat_id,
+        &chat_title,
+        workspaces,
+        settings,
+        storage,
+        forwarded,
+    )
+    .await
+}
+
+/// Creates an issue in `chat_id` from the messages `user` sent or forwarded, and subscribes them
+/// to its updates.
+#[allow(clippy::too_many_arguments)]
+async fn create_forwarded_issue(
+    bot: &Bot,
+    user: &User,
+    chat_id: ChatId,
+    chat_title: &str,
+    workspaces: &PylonWorkspaces,
+    settings: &Settings,
+    storage: &Storage,
+    forwarded: &ForwardedMessages,
+) -> eyre::Result<()> {
+    // The messages may have expired or been cancelled while the chat was chosen
+    let messages = forwarded.messages(user.id);
+    let Some(first_message) = messages.first() else {
+        bot.send_message(user.id, "⚠️ Please send or forward me the messages again")
+            .await?;
+        return Ok(());
+    };
+    let args = forwarded.args(user.id);
+    let (first_line, details) = split_title(&args);
+    let mut issue_args = IssueArgs {
+        force: false,
+        requester: Some(user.id),
+        ..IssueArgs::parse(first_line)
+    };
+
+    if issue_args.title.is_empty() {
+        issue_args.title = title_from_message(first_message);
+    }
+
+    let pylon_client = workspaces.for_chat(settings, &chat_id.to_string())?;
+
+    let options = match issue_args.resolve(&pylon_client).await? {
+        Ok(options) => options,
+        Err(errors) => {
+            bot.send_message(user.id, format!("⚠️ {}", errors.join("\n")))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let body = [details.to_string()]
+        .into_iter()
+        .chain(messages.iter().filter_map(forwarded_text))
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let issue = create_issue(
+        bot,
+        IssueSource {
+            chat_id,
+            chat_title,
+            topic_id: None,
+            message_id: None,
+            author: display_name(Some(user)),
+            body: &body,
+            attachments: messages.iter().filter_map(message_attachment).collect(),
+        },
+        &issue_args,
+        options,
+        &pylon_client,
+        settings,
+        storage,
+    )
+    .await?;
+
+    forwarded.clear(user.id);
+
+    if !issue.id.is_empty() {
+        storage.set_subscription(&issue.id, user.id.0, true).await?;
+    }
+
+    info!(
+        "Issue #{} created from {} private messages of {}",
+        issue.number,
+        messages.len(),
+        user.id
+    );
+
+    bot.send_message(
+        user.id,
+        format!(
+            "✅ New issue [\\#{}]({}) created in Pylon for {}\\. You will be notified of its \
+             updates\\.",
+            issue.number,
+            issue.link,
+            escape_markdown_v2(chat_title)
+        ),
+    )
+    .parse_mode(ParseMode::MarkdownV2)
+    .await?;
+
+    Ok(())
+}
+
+/// Linked chats of the bot `bot_name` that `user_id` is a member of, with their title.
+async fn member_chats(
+    bot: &Bot,
+    user_id: UserId,
+    bot_name: &str,
+    settings: &Settings,
+) -> Vec<(ChatId, String)> {
+    let mut chats = Vec::new();
+
+    for (chat_id, pylon_account_id) in settings.bot_chats(bot_name) {
+        let Ok(chat_id) = chat_id.parse().map(ChatId) else {
+            continue;
+        };
+
+        if !pylon_account_id.is_empty() && is_chat_member(bot, chat_id, user_id).await {
+            chats.push((chat_id, chat_title(bot, chat_id).await));
+        }
+    }
+
+    chats
+}
+
+async fn is_chat_member(bot: &Bot, chat_id: ChatId, user_id: UserId) -> bool {
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member.is_present(),
+        Err(err) => {
+            debug!("Failed to get the membership of {user_id} in {chat_id}: {err}");
+            false
+        }
+    }
+}
+
+async fn chat_title(bot: &Bot, chat_id: ChatId) -> String {
+    match bot.get_chat(chat_id).await {
+        Ok(chat) => chat.title().unwrap_or_default().to_string(),
+        Err(_) => chat_id.to_string(),
+    }
+}
+
+/// Text of a forwarded message, preceded by its original sender.
+fn forwarded_text(message: &Message) -> Option<String> {
+    let text = message_text(message)?;
+    let sender = match message.forward_origin() {
+        Some(MessageOrigin::User { sender_user, .. }) => display_name(Some(sender_user)),
+        Some(MessageOrigin::HiddenUser {
+            sender_user_name, ..
+        }) => sender_user_name.clone(),
+        Some(MessageOrigin::Chat { sender_chat, .. }) => {
+            sender_chat.title().unwrap_or_default().to_string()
+        }
+        Some(MessageOrigin::Channel { chat, .. }) => chat.title().unwrap_or_default().to_string(),
+        None => return Some(text.to_string()),
+    };
+
+    Some(format!("{sender}: {text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serde_json::{Value, json};
+    use teloxide::types::{Message, UserId};
+
+    use super::{
+        Bundle, FORWARDED_MESSAGES_LIFETIME, ForwardedMessages, MAX_FORWARDED_MESSAGES,
+        forwarded_text,
+    };
+
+    fn private_message(text: &str, forward_origin: Option<Value>) -> Message {
+        let mut message = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 42, "type": "private", "first_name": "Bob"},
+            "from": {"id": 42, "is_bot": false, "first_name": "Bob"},
+            "text": text
+        });
+
+        if let Some(forward_origin) = forward_origin {
+            message["forward_origin"] = forward_origin;
+        }
+
+        serde_json::from_value(message).unwrap()
+    }
+
+    #[test]
+    fn test_forwarded_text_senders() {
+        let text = |forward_origin| forwarded_text(&private_message("Login fails", forward_origin));
+
+        assert_eq!(text(None).unwrap(), "Login fails");
+        assert_eq!(
+            text(Some(json!({
+                "type": "user",
+                "date": 0,
+                "sender_user": {"id": 7, "is_bot": false, "first_name": "Carol", "username": "carol"}
+            })))
+            .unwrap(),
+            "carol: Login fails"
+        );
+        assert_eq!(
+            text(Some(json!({
+                "type": "user",
+                "date": 0,
+                "sender_user": {"id": 7, "is_bot": false, "first_name": "Carol", "last_name": "Doe"}
+            })))
+            .unwrap(),
+            "Carol Doe: Login fails"
+        );
+        assert_eq!(
+            text(Some(json!({
+                "type": "hidden_user",
+                "date": 0,
+                "sender_user_name": "Dan"
+            })))
+            .unwrap(),
+            "Dan: Login fails"
+        );
+        assert_eq!(
+            text(Some(json!({
+                "type": "chat",
+                "date": 0,
+                "sender_chat": {"id": -100123, "type": "supergroup", "title": "ACME support"}
+            })))
+            .unwrap(),
+            "ACME support: Login fails"
+        );
+        assert_eq!(
+            text(Some(json!({
+                "type": "channel",
+                "date": 0,
+                "chat": {"id": -100456, "type": "channel", "title": "ACME status"},
+                "message_id": 5
+            })))
+            .unwrap(),
+            "ACME status: Login fails"
+        );
+    }
+
+    #[test]
+    fn test_forwarded_messages_are_capped() {
+        let forwarded = ForwardedMessages::new();
+        let user_id = UserId(42);
+
+        for count in 1..=MAX_FORWARDED_MESSAGES {
+            assert_eq!(
+                forwarded.push(user_id, private_message("Login fails", None)),
+                Some(count)
+            );
+        }
+
+        assert_eq!(
+            forwarded.push(user_id, private_message("Login fails", None)),
+            None
+        );
+        assert_eq!(forwarded.messages(user_id).len(), MAX_FORWARDED_MESSAGES);
+        assert!(forwarded.messages(UserId(7)).is_empty());
+
+        forwarded.clear(user_id);
+        assert!(forwarded.messages(user_id).is_empty());
+    }
+
+    #[test]
+    fn test_idle_forwarded_messages_expire() {
+        let forwarded = ForwardedMessages::new();
+        let Some(idle_since) =
+            Instant::now().checked_sub(FORWARDED_MESSAGES_LIFETIME + Duration::from_secs(1))
+        else {
+            return;
+        };
+
+        forwarded.users.lock().unwrap().insert(
+            UserId(7),
+            Bundle {
+                messages: vec![private_message("Login fails", None)],
+                args: String::new(),
+                updated_at: idle_since,
+            },
+        );
+        forwarded.push(UserId(42), private_message("Login fails", None));
+
+        assert!(forwarded.messages(UserId(7)).is_empty());
+        assert_eq!(forwarded.messages(UserId(42)).len(), 1);
+        assert_eq!(forwarded.users.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,435 @@
+use std::sync::Arc;
+
+use teloxide::{
+    Bot,
+    payloads::SendMessageSetters,
+    types::{
+        ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, ParseMode, ThreadId, User,
+        UserId,
+    },
+    utils::html,
+};
+use tracing::info;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    config::{Config, Settings},
+    endpoints::{
+        NewIssueDialogue, State,
+        callback::CallbackData,
+        is_answering_other_user,
+        status::chat_issue,
+        status::describe_issue,
+        text_to_html,
+        topics::{reply_in_topic, send_to_topic, topic_id},
+    },
+    pylon::{IssueResponse, IssueUpdate, Note, PylonClient, PylonWorkspaces},
+    storage::Storage,
+};
+
+/// State of the issues reopened from Telegram.
+const REOPENED_STATE: &str = "waiting_on_you";
+
+/// Buttons attached to the confirmation of a new issue.
+pub fn issue_keyboard(number: u64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Close", CallbackData::CloseIssue { number }.to_string()),
+        InlineKeyboardButton::callback(
+            "Add comment",
+            CallbackData::CommentIssue { number }.to_string(),
+        ),
+    ]])
+}
+
+/// Handles `/close <number> [reason]`.
+pub async fn close_issue(
+    bot: &Bot,
+    message: &Message,
+    args: &str,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let (number, reason) = split_number(args);
+    let settings = config.get().await;
+
+    if let Some((issue, pylon_client)) = authorized_issue(
+        bot,
+        message.chat.id,
+        topic_id(message),
+        message.from.as_ref(),
+        number,
+        workspaces,
+        &settings,
+    )
+    .await?
+    {
+        let author = display_user(message.from.as_ref());
+
+        if !reason.is_empty() {
+            add_note(&issue, &author, reason, &pylon_client).await?;
+        }
+
+        set_state(
+            bot,
+            message.chat.id,
+            topic_id(message),
+            &issue,
+            "closed",
+            message.from.as_ref(),
+            &pylon_client,
+            &storage,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Handles `/reopen <number>`.
+pub async fn reopen_issue(
+    bot: &Bot,
+    message: &Message,
+    args: &str,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let (number, _) = split_number(args);
+    let settings = config.get().await;
+
+    if let Some((issue, pylon_client)) = authorized_issue(
+        bot,
+        message.chat.id,
+        topic_id(message),
+        message.from.as_ref(),
+        number,
+        workspaces,
+        &settings,
+    )
+    .await?
+    {
+        set_state(
+            bot,
+            message.chat.id,
+            topic_id(message),
+            &issue,
+            REOPENED_STATE,
+            message.from.as_ref(),
+            &pylon_client,
+            &storage,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Handles `/comment <number> <text>`.
+pub async fn comment_issue(
+    bot: &Bot,
+    message: &Message,
+    args: &str,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+) -> eyre::Result<()> {
+    let (number, text) = split_number(args);
+    let settings = config.get().await;
+
+    if text.is_empty() {
+        reply_in_topic(bot, message, "⚠️ Usage: /comment <number> <text>").await?;
+        return Ok(());
+    }
+
+    if let Some((issue, pylon_client)) = authorized_issue(
+        bot,
+        message.chat.id,
+        topic_id(message),
+        message.from.as_ref(),
+        number,
+        workspaces,
+        &settings,
+    )
+    .await?
+    {
+        let author = display_user(message.from.as_ref());
+
+        add_note(&issue, &author, text, &pylon_client).await?;
+
+        reply_in_topic(
+            bot,
+            message,
+            format!(
+                "✅ Comment added to issue [\\#{}]({})",
+                issue.number.unwrap_or_default(),
+                issue.link.clone().unwrap_or_default()
+            ),
+        )
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Handles the "Close" button of an issue confirmation.
+#[allow(clippy::too_many_arguments)]
+pub async fn close_issue_button(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    user: &User,
+    number: u64,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+
+    if let Some((issue, pylon_client)) = authorized_issue(
+        bot,
+        chat_id,
+        topic_id,
+        Some(user),
+        &number.to_string(),
+        workspaces,
+        &settings,
+    )
+    .await?
+    {
+        set_state(
+            bot,
+            chat_id,
+            topic_id,
+            &issue,
+            "closed",
+            Some(user),
+            &pylon_client,
+            &storage,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Handles the "Add comment" button of an issue confirmation, asking for the comment.
+#[allow(clippy::too_many_arguments)]
+pub async fn comment_issue_button(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    user: &User,
+    number: u64,
+    dialogue: NewIssueDialogue,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+
+    if is_answering_other_user(&dialogue, user.id).await? {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            format!("⚠️ Someone else is answering a prompt, please use /comment {number} <text>"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if authorized_issue(
+        bot,
+        chat_id,
+        topic_id,
+        Some(user),
+        &number.to_string(),
+        workspaces,
+        &settings,
+    )
+    .await?
+    .is_some()
+    {
+        dialogue
+            .update(State::WaitingForComment {
+                user_id: user.id,
+                number,
+            })
+            .await?;
+
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            format!("Please enter your comment on issue #{number}:"),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_comment_input(
+    bot: Bot,
+    message: Message,
+    dialogue: NewIssueDialogue,
+    (_, number): (UserId, u64),
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+) -> eyre::Result<()> {
+    // Reset dialogue to start
+    dialogue.update(State::Start).await?;
+
+    match message.text() {
+        Some(text) if !text.starts_with('/') => {
+            comment_issue(
+                &bot,
+                &message,
+                &format!("{number} {text}"),
+                &workspaces,
+                config,
+            )
+            .await
+        }
+        _ => {
+            reply_in_topic(&bot, &message, "Comment cancelled").await?;
+            Ok(())
+        }
+    }
+}
+
+/// Gets the issue `number` of the account of the chat or forum topic, with the client of the
+/// chat's workspace, if `user` is allowed to act on the chat's issues, and explains why otherwise.
+async fn authorized_issue(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    user: Option<&User>,
+    number: &str,
+    workspaces: &PylonWorkspaces,
+    settings: &Settings,
+) -> eyre::Result<Option<(IssueResponse, Arc<PylonClient>)>> {
+    let chat_settings = settings.chat_settings(&chat_id.to_string());
+    let username = user.and_then(|user| user.username.as_deref());
+
+    if !chat_settings.issue_actions.allows(username, settings) {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            "⚠️ You are not allowed to update issues",
+        )
+        .await?;
+        return Ok(None);
+    }
+
+    let Some(account_id) =
+        settings.topic_account(&chat_id.to_string(), topic_id.map(|topic_id| topic_id.0.0))
+    else {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            "⚠️ This chat is not linked to a Pylon account",
+        )
+        .await?;
+        return Ok(None);
+    };
+
+    let pylon_client = workspaces.for_chat(settings, &chat_id.to_string())?;
+
+    let Some(issue) = chat_issue(&pylon_client, account_id, number).await? else {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            format!("⚠️ Issue {} not found", number.trim_start_matches('#')),
+        )
+        .await?;
+        return Ok(None);
+    };
+
+    Ok(Some((issue, pylon_client)))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn set_state(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    issue: &IssueResponse,
+    state: &str,
+    user: Option<&User>,
+    pylon_client: &PylonClient,
+    storage: &Storage,
+) -> eyre::Result<()> {
+    let issue = pylon_client
+        .update_issue(
+            &issue.id,
+            &IssueUpdate {
+                state: Some(state.to_string()),
+            },
+        )
+        .await?;
+
+    let number = issue.number.unwrap_or_default();
+
+    info!("Issue #{number} set to '{state}' by {}", display_user(user));
+
+    // Record the state so that the change is not notified again
+    storage.set_issue_state(&issue.id, state).await?;
+
+    let action = if state == "closed" {
+        AuditAction::IssueClose
+    } else {
+        AuditAction::IssueReopen
+    };
+
+    storage
+        .audit_log()
+        .record(AuditEntry::new(
+            user.map(|user| user.id.0),
+            action,
+            format!("#{number}"),
+        ))
+        .await?;
+
+    send_to_topic(bot, chat_id, topic_id, describe_issue(&issue))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn add_note(
+    issue: &IssueResponse,
+    author: &str,
+    text: &str,
+    pylon_client: &PylonClient,
+) -> eyre::Result<()> {
+    let note = Note {
+        body_html: format!(
+            "<b>{}</b> on Telegram:<br>{}",
+            html::escape(author),
+            text_to_html(text)
+        ),
+    };
+
+    pylon_client.create_note(&issue.id, &note).await
+}
+
+/// Splits command arguments into the issue number and the rest.
+fn split_number(args: &str) -> (&str, &str) {
+    let args = args.trim();
+    let (number, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+
+    (number, rest.trim())
+}
+
+fn display_user(user: Option<&User>) -> String {
+    user.map(|user| match &user.username {
+        Some(username) => format!("@{username}"),
+        None => user.full_name(),
+    })
+    .unwrap_or_default()
+}
@@ -0,0 +1,192 @@
+use teloxide::types::UserId;
+
+use crate::pylon::{PRIORITIES, PylonClient};
+
+/// `/issue` flag creating a new issue even if the message is already tracked in one.
+pub const FORCE_FLAG: &str = "--force";
+
+/// Title and options given on the first line of `/issue`, e.g.
+/// `/issue Checkout fails #urgent +billing @alice`:
+/// - `#<priority>` sets the priority,
+/// - `+<tag>` adds a tag,
+/// - `@<user>` assigns the issue to a Pylon user, designated by email or name,
+/// - `--force` creates the issue even if the message already has one.
+///
+/// Only the options ending the line are parsed, the words before them make the title. `#` followed
+/// by a number is a reference rather than a priority, and a leading `\` keeps a word such as
+/// `\@bob` in the title.
+#[derive(Debug, Default, Clone)]
+pub struct IssueArgs {
+    pub title: String,
+    pub priority: Option<String>,
+    pub tags: Vec<String>,
+    pub assignee: Option<String>,
+    pub force: bool,
+    /// Telegram user who asked for the issue, none when created by a rule.
+    pub requester: Option<UserId>,
+    /// Name of the forum topic the issue comes from, if known.
+    pub topic: Option<String>,
+}
+
+/// Pylon values resolved from [`IssueArgs`].
+#[derive(Debug, Default)]
+pub struct IssueOptions {
+    pub priority: Option<String>,
+    pub tags: Vec<String>,
+    pub assignee_id: Option<String>,
+}
+
+impl IssueArgs {
+    pub fn parse(line: &str) -> Self {
+        let mut args = IssueArgs::default();
+        let mut words = line.split_whitespace().collect::<Vec<_>>();
+
+        while let Some(word) = words.last() {
+            if *word == FORCE_FLAG {
+                args.force = true;
+            } else if let Some(priority) = word
+                .strip_prefix('#')
+                .filter(|p| !p.is_empty() && !p.chars().all(|c| c.is_ascii_digit()))
+            {
+                // The first priority of the line wins, as it's the last one parsed
+                args.priority = Some(priority.to_string());
+            } else if let Some(tag) = word.strip_prefix('+').filter(|t| !t.is_empty()) {
+                args.tags.insert(0, tag.to_string());
+            } else if let Some(assignee) = word.strip_prefix('@').filter(|a| !a.is_empty()) {
+                args.assignee = Some(assignee.to_string());
+            } else {
+                break;
+            }
+
+            words.pop();
+        }
+
+        args.title = words
+            .iter()
+            .map(|word| match word.strip_prefix('\\') {
+                Some(escaped) if escaped.starts_with(['#', '+', '@', '-']) => escaped,
+                _ => word,
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        args
+    }
+
+    /// Checks the options against the values known to Pylon.
+    ///
+    /// Returns the resolved options, or the list of invalid options as user facing messages.
+    pub async fn resolve(
+        &self,
+        pylon_client: &PylonClient,
+    ) -> eyre::Result<Result<IssueOptions, Vec<String>>> {
+        let mut options = IssueOptions::default();
+        let mut errors = Vec::new();
+
+        if let Some(priority) = &self.priority {
+            let priority = priority.to_lowercase();
+
+            if PRIORITIES.contains(&priority.as_str()) {
+                options.priority = Some(priority);
+            } else {
+                errors.push(format!(
+                    "Unknown priority #{priority}, expected one of: {}",
+                    PRIORITIES.map(|p| format!("#{p}")).join(", ")
+                ));
+            }
+        }
+
+        if !self.tags.is_empty() {
+            let known_tags = pylon_client.get_tags().await?;
+
+            for tag in &self.tags {
+                if let Some(known) = known_tags
+                    .iter()
+                    .find(|t| t.value.eq_ignore_ascii_case(tag))
+                {
+                    options.tags.push(known.value.clone());
+                } else {
+                    errors.push(format!("Unknown tag +{tag}"));
+                }
+            }
+
+            if errors.iter().any(|e| e.starts_with("Unknown tag")) {
+                errors.push(format!(
+                    "Available tags: {}",
+                    known_tags
+                        .iter()
+                        .map(|t| format!("+{}", t.value))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+        }
+
+        if let Some(assignee) = &self.assignee {
+            let users = pylon_client.get_users().await?;
+            let matching = users
+                .iter()
+                .filter(|u| u.matches_handle(assignee))
+                .collect::<Vec<_>>();
+
+            match matching.as_slice() {
+                [user] => options.assignee_id = Some(user.id.clone()),
+                [] => errors.push(format!("Unknown Pylon user @{assignee}")),
+                _ => errors.push(format!("@{assignee} matches several Pylon users")),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Ok(options))
+        } else {
+            Ok(Err(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueArgs;
+
+    #[test]
+    fn test_parse_trailing_options() {
+        let args = IssueArgs::parse("Checkout fails #urgent +billing +web @alice --force");
+
+        assert_eq!(args.title, "Checkout fails");
+        assert_eq!(args.priority.as_deref(), Some("urgent"));
+        assert_eq!(args.tags, vec!["billing", "web"]);
+        assert_eq!(args.assignee.as_deref(), Some("alice"));
+        assert!(args.force);
+    }
+
+    #[test]
+    fn test_parse_options_inside_title() {
+        let args = IssueArgs::parse("Ask @bob about +2 errors #high");
+
+        assert_eq!(args.title, "Ask @bob about +2 errors");
+        assert_eq!(args.priority.as_deref(), Some("high"));
+        assert!(args.assignee.is_none() && args.tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_references_and_escapes() {
+        let args = IssueArgs::parse("Error on block #500");
+        assert_eq!(args.title, "Error on block #500");
+        assert!(args.priority.is_none());
+
+        let args = IssueArgs::parse("Ask \\@bob \\+1 \\--force");
+        assert_eq!(args.title, "Ask @bob +1 --force");
+        assert!(args.assignee.is_none() && args.tags.is_empty() && !args.force);
+
+        assert_eq!(IssueArgs::parse("Path C:\\temp").title, "Path C:\\temp");
+    }
+
+    #[test]
+    fn test_parse_only_options() {
+        let args = IssueArgs::parse("  #low   +billing ");
+
+        assert_eq!(args.title, "");
+        assert_eq!(args.priority.as_deref(), Some("low"));
+        assert_eq!(args.tags, vec!["billing"]);
+        assert!(IssueArgs::parse("").title.is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use teloxide::types::{ChatId, Message, MessageId, ThreadId};
+
+use crate::config::Config;
+
+/// Number of messages kept per chat.
+const MESSAGES_PER_CHAT: usize = 1000;
+
+/// Time after which a message is dropped, even if its chat is quiet.
+const MESSAGE_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Recent messages of linked chats. Some updates, such as reactions, only reference a message by
+/// id, and the Bot API can't fetch a message from its id.
+///
+/// The names of the forum topics are also kept, since messages only reference their topic by id.
+#[derive(Default)]
+pub struct MessageCache {
+    chats: Mutex<HashMap<ChatId, VecDeque<(Instant, Message)>>>,
+    topics: Mutex<HashMap<(ChatId, ThreadId), String>>,
+}
+
+impl MessageCache {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn insert(&self, message: Message) {
+        if let Some((topic_id, name)) = topic_change(&message) {
+            self.topics
+                .lock()
+                .unwrap()
+                .insert((message.chat.id, topic_id), name);
+        }
+
+        let mut chats = self.chats.lock().unwrap();
+
+        // Messages are kept in the order they were received, the oldest are first
+        chats.retain(|_, messages| {
+            while messages
+                .front()
+                .is_some_and(|(received_at, _)| received_at.elapsed() >= MESSAGE_LIFETIME)
+            {
+                messages.pop_front();
+            }
+
+            !messages.is_empty()
+        });
+
+        let messages = chats.entry(message.chat.id).or_default();
+
+        if messages.len() == MESSAGES_PER_CHAT {
+            messages.pop_front();
+        }
+
+        messages.push_back((Instant::now(), message));
+    }
+
+    pub fn get(&self, chat_id: ChatId, message_id: MessageId) -> Option<Message> {
+        self.chats
+            .lock()
+            .unwrap()
+            .get(&chat_id)?
+            .iter()
+            .rev()
+            .find(|(received_at, message)| {
+                message.id == message_id && received_at.elapsed() < MESSAGE_LIFETIME
+            })
+            .map(|(_, message)| message.clone())
+    }
+
+    pub fn topic_name(&self, chat_id: ChatId, topic_id: ThreadId) -> Option<String> {
+        self.topics
+            .lock()
+            .unwrap()
+            .get(&(chat_id, topic_id))
+            .cloned()
+    }
+}
+
+/// Forum topic created or renamed by `message`, or whose creation it replies to.
+fn topic_change(message: &Message) -> Option<(ThreadId, String)> {
+    if let Some(topic) = message.forum_topic_created() {
+        // The creation of a topic starts its thread
+        let topic_id = message.thread_id.unwrap_or(ThreadId(message.id));
+
+        return Some((topic_id, topic.name.clone()));
+    }
+
+    let topic_id = message.thread_id.filter(|_| message.is_topic_message)?;
+    let name = message
+        .forum_topic_edited()
+        .and_then(|topic| topic.name.clone())
+        .or_else(|| {
+            message
+                .reply_to_message()
+                .and_then(|replied| replied.forum_topic_created())
+                .map(|topic| topic.name.clone())
+        })?;
+
+    Some((topic_id, name))
+}
+
+/// Caches the messages of linked chats, the only ones whose reactions are handled.
+pub async fn cache_message(message: Message, cache: Arc<MessageCache>, config: Arc<Config>) {
+    let linked = config
+        .get()
+        .await
+        .pylon_account(&message.chat.id.to_string())
+        .is_some();
+
+    if linked {
+        cache.insert(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serde_json::json;
+    use teloxide::types::{ChatId, Message, MessageId};
+
+    use super::{MESSAGE_LIFETIME, MessageCache};
+
+    fn group_message(chat_id: i64, message_id: i32) -> Message {
+        serde_json::from_value(json!({
+            "message_id": message_id,
+            "date": 0,
+            "chat": {"id": chat_id, "type": "supergroup", "title": "Acme"},
+            "from": {"id": 42, "is_bot": false, "first_name": "Bob"},
+            "text": "Login fails"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_old_messages_expire() {
+        let cache = MessageCache::new();
+        let Some(received_at) =
+            Instant::now().checked_sub(MESSAGE_LIFETIME + Duration::from_secs(1))
+        else {
+            return;
+        };
+
+        cache
+            .chats
+            .lock()
+            .unwrap()
+            .entry(ChatId(-100123))
+            .or_default()
+            .push_back((received_at, group_message(-100123, 1)));
+
+        assert!(cache.get(ChatId(-100123), MessageId(1)).is_none());
+
+        cache.insert(group_message(-100456, 2));
+
+        assert!(cache.get(ChatId(-100456), MessageId(2)).is_some());
+        assert_eq!(cache.chats.lock().unwrap().len(), 1);
+    }
+}
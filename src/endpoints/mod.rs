@@ -0,0 +1,1704 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use chrono::Utc;
+use eyre::{OptionExt, WrapErr};
+use serde::{Deserialize, Serialize};
+use teloxide::{
+    Bot,
+    dispatching::dialogue::InMemStorage,
+    net::Download,
+    payloads::{AnswerCallbackQuerySetters, SendMessageSetters},
+    prelude::{Dialogue, Requester},
+    types::{
+        CallbackQuery, ChatAction, ChatId, ChatKind, ChatMemberStatus, FileId, ForceReply,
+        InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId, MessageKind, ParseMode,
+        ReplyParameters, User, UserId,
+    },
+    utils::{command::BotCommands, html},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    alerts::{AlertContext, command_name},
+    audit::{AuditAction, AuditEntry},
+    bots::BotIdentity,
+    config::{Config, Settings},
+    logging::Redacted,
+    metrics,
+    pylon::{CustomFieldValue, Issue, PylonClient, PylonWorkspaces},
+    storage::{IssueRecord, Storage},
+};
+
+mod audit;
+mod callback;
+mod chat_defaults;
+mod forwards;
+mod issue_actions;
+mod issue_args;
+mod message_cache;
+mod reactions;
+mod rules;
+mod status;
+mod subscriptions;
+mod topics;
+use audit::show_audit;
+pub use callback::CallbackData;
+pub use chat_defaults::handle_chat_default_input;
+use chat_defaults::{DefaultField, chat_defaults, show_chat_defaults};
+use forwards::forward_to_chat;
+pub use forwards::{
+    ForwardedMessages, PrivateCommand, handle_private_message, process_private_command,
+};
+pub use issue_actions::handle_comment_input;
+use issue_actions::{
+    close_issue, close_issue_button, comment_issue, comment_issue_button, issue_keyboard,
+    reopen_issue,
+};
+use issue_args::{FORCE_FLAG, IssueArgs, IssueOptions};
+pub use message_cache::{MessageCache, cache_message};
+pub use reactions::handle_reaction;
+use rules::{RULE_PROMPT, chat_rules, delete_rule, show_chat_rules};
+pub use rules::{handle_rule_input, handle_rule_match, matching_rule};
+pub use status::IssueLists;
+use status::{issue_status, list_open_issues};
+use subscriptions::set_subscription;
+use topics::{replied_message, reply_in_topic, send_to_topic, topic_id, topic_name};
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase")]
+pub enum Command {
+    /// Display this text.
+    #[command(aliases = ["h", "?"])]
+    Help,
+
+    /// Create an issue. Reply to a message with `/issue <title>`, or send `/issue <title>` followed
+    /// by the description on the next lines. End the title line with `#priority`, `+tag` or
+    /// `@assignee` to set them. Admins can add `--force` to create a new issue for a message that
+    /// already has one.
+    #[command()]
+    Issue(String),
+
+    /// Show the status of an issue: `/status <number>`.
+    #[command()]
+    Status(String),
+
+    /// List the open issues of this chat.
+    #[command()]
+    Issues,
+
+    /// Close an issue: `/close <number> [reason]`.
+    #[command()]
+    Close(String),
+
+    /// Reopen an issue: `/reopen <number>`.
+    #[command()]
+    Reopen(String),
+
+    /// Comment an issue: `/comment <number> <text>`.
+    #[command()]
+    Comment(String),
+
+    /// Receive the updates of an issue in private: `/subscribe <number>`.
+    #[command()]
+    Subscribe(String),
+
+    /// Stop receiving the updates of an issue: `/unsubscribe <number>`.
+    #[command()]
+    Unsubscribe(String),
+}
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase")]
+pub enum AdminCommand {
+    /// Display this text.
+    #[command(aliases = ["h", "?"])]
+    Help,
+
+    /// List all active chats, optionally of a Pylon workspace: /active [workspace].
+    #[command()]
+    Active(String),
+
+    /// List all TG chats not linked to a Pulon account.
+    #[command()]
+    Unlinked,
+
+    /// List all TG chats configured in the DB but where the bot is not a member.
+    #[command()]
+    Orphans,
+
+    /// Link a chat to a Pylon account, optionally of another workspace: /link [workspace].
+    #[command()]
+    Link(String),
+
+    /// Edit the defaults applied to issues created from a chat.
+    #[command()]
+    Defaults,
+
+    /// Edit the rules creating issues from the messages of a chat.
+    #[command()]
+    Rules,
+
+    /// Show the latest audit entries, optionally filtered: /audit [filter].
+    #[command()]
+    Audit(String),
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum State {
+    #[default]
+    Start,
+    WaitingForAccountId {
+        user_id: UserId,
+        chat_id: String,
+        workspace: Option<String>,
+    },
+    WaitingForIssueTitle {
+        user_id: UserId,
+    },
+    WaitingForIssueDescription {
+        user_id: UserId,
+        title: String,
+    },
+    WaitingForChatDefault {
+        user_id: UserId,
+        chat_id: String,
+        field: DefaultField,
+    },
+    WaitingForRule {
+        user_id: UserId,
+        chat_id: String,
+    },
+    WaitingForComment {
+        user_id: UserId,
+        number: u64,
+    },
+}
+
+impl State {
+    /// Whether `message` answers this dialogue. In group chats, only the user who started the
+    /// dialogue can answer it.
+    pub fn is_answered_by(&self, message: &Message) -> bool {
+        match self.user_id() {
+            Some(user_id) => message.from.as_ref().map(|user| user.id) == Some(user_id),
+            None => true,
+        }
+    }
+
+    /// User who started this dialogue.
+    fn user_id(&self) -> Option<UserId> {
+        match self {
+            State::Start => None,
+            State::WaitingForAccountId { user_id, .. }
+            | State::WaitingForIssueTitle { user_id }
+            | State::WaitingForIssueDescription { user_id, .. }
+            | State::WaitingForChatDefault { user_id, .. }
+            | State::WaitingForRule { user_id, .. }
+            | State::WaitingForComment { user_id, .. } => Some(*user_id),
+        }
+    }
+}
+
+type LinkToPylonAccountDialogue = Dialogue<State, InMemStorage<State>>;
+type NewIssueDialogue = Dialogue<State, InMemStorage<State>>;
+
+/// Whether a user other than `user_id` is answering a prompt in the chat. Dialogues are kept per
+/// chat, so starting one would silently cancel theirs.
+async fn is_answering_other_user(
+    dialogue: &NewIssueDialogue,
+    user_id: UserId,
+) -> eyre::Result<bool> {
+    let state = dialogue.get().await?.unwrap_or_default();
+
+    Ok(state.user_id().is_some_and(|other| other != user_id))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn process_command(
+    bot: Bot,
+    message: Message,
+    cmd: Command,
+    dialogue: NewIssueDialogue,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+    issue_lists: Arc<IssueLists>,
+) -> eyre::Result<()> {
+    count_command(&message);
+    let context = AlertContext::for_message(&message);
+
+    async move {
+        match cmd {
+            Command::Help => {
+                reply_in_topic(&bot, &message, Command::descriptions().to_string()).await?;
+            }
+            Command::Issue(args) => {
+                new_issue(
+                    args,
+                    &bot,
+                    message,
+                    dialogue,
+                    &workspaces,
+                    config,
+                    storage,
+                    cache,
+                )
+                .await?
+            }
+            Command::Status(number) => {
+                issue_status(
+                    &bot,
+                    message.chat.id,
+                    topic_id(&message),
+                    &number,
+                    &workspaces,
+                    config,
+                )
+                .await?
+            }
+            Command::Issues => {
+                list_open_issues(
+                    &bot,
+                    message.chat.id,
+                    topic_id(&message),
+                    0,
+                    None,
+                    &workspaces,
+                    config,
+                    &issue_lists,
+                )
+                .await?
+            }
+            Command::Close(args) => {
+                close_issue(&bot, &message, &args, &workspaces, config, storage).await?
+            }
+            Command::Reopen(args) => {
+                reopen_issue(&bot, &message, &args, &workspaces, config, storage).await?
+            }
+            Command::Comment(args) => {
+                comment_issue(&bot, &message, &args, &workspaces, config).await?
+            }
+            Command::Subscribe(number) => {
+                set_subscription(&bot, &message, &number, true, storage).await?
+            }
+            Command::Unsubscribe(number) => {
+                set_subscription(&bot, &message, &number, false, storage).await?
+            }
+        };
+
+        eyre::Ok(())
+    }
+    .await
+    .wrap_err(context)
+}
+
+pub async fn process_admin_command(
+    bot: Bot,
+    message: Message,
+    cmd: AdminCommand,
+    identity: BotIdentity,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    count_command(&message);
+    let context = AlertContext::for_message(&message);
+
+    async move {
+        if is_public_chat(message.clone()) {
+            warn!("Admin commands are only authorized in a private chat with the bot");
+            return Ok(());
+        }
+
+        let settings = config.get().await;
+
+        if !is_bot_admin(message.from.as_ref(), &settings) {
+            if let AdminCommand::Help = cmd {
+                bot.send_message(message.chat.id, PrivateCommand::descriptions().to_string())
+                    .await?;
+            } else {
+                warn!("Unauthorized call to admin command");
+            }
+
+            return Ok(());
+        }
+
+        match cmd {
+            AdminCommand::Help => {
+                bot.send_message(message.chat.id, AdminCommand::descriptions().to_string())
+                    .await?;
+            }
+            AdminCommand::Active(workspace) => {
+                active(
+                    &bot,
+                    message.chat.id,
+                    &identity.name,
+                    workspace.trim(),
+                    workspaces,
+                    settings,
+                )
+                .await?
+            }
+            AdminCommand::Unlinked => {
+                unlinked(&bot, message.chat.id, &identity.name, settings).await?
+            }
+            AdminCommand::Orphans => {
+                orphans(&bot, message.chat.id, &identity.name, settings).await?
+            }
+            AdminCommand::Link(workspace) => {
+                link_chat_to_account(
+                    &bot,
+                    message.chat.id,
+                    &identity.name,
+                    workspace.trim(),
+                    workspaces,
+                    settings,
+                )
+                .await?
+            }
+            AdminCommand::Defaults => {
+                chat_defaults(&bot, message.chat.id, &identity.name, settings).await?
+            }
+            AdminCommand::Rules => {
+                chat_rules(&bot, message.chat.id, &identity.name, settings).await?
+            }
+            AdminCommand::Audit(filter) => {
+                show_audit(&bot, message.chat.id, &filter, &storage).await?
+            }
+        }
+
+        eyre::Ok(())
+    }
+    .await
+    .wrap_err(context)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    dialogue: LinkToPylonAccountDialogue,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+    forwarded: Arc<ForwardedMessages>,
+    issue_lists: Arc<IssueLists>,
+) -> eyre::Result<()> {
+    let context = AlertContext::for_callback(&q);
+
+    async move {
+        let Some(data) = q.data.as_deref().and_then(CallbackData::parse) else {
+            return Ok(());
+        };
+
+        let Some(message) = q.message.as_ref() else {
+            warn!("Can't answer callback query without message");
+            return Ok(());
+        };
+        let chat_id = message.chat().id;
+        let topic = message.regular_message().and_then(topic_id);
+        let settings = config.get().await;
+
+        if !data
+            .allowed(&settings.chat_settings(&chat_id.to_string()))
+            .allows(q.from.username.as_deref(), &settings)
+        {
+            warn!("Unauthorized callback query");
+            bot.answer_callback_query(q.id.clone())
+                .text("⚠️ You are not allowed to do this")
+                .await?;
+            return Ok(());
+        }
+
+        // Answer the callback to remove loading state
+        bot.answer_callback_query(q.id.clone()).await?;
+
+        match data {
+            CallbackData::Link {
+                chat_id: linked_chat_id,
+                workspace,
+            } => {
+                // Update dialogue state
+                dialogue
+                    .update(State::WaitingForAccountId {
+                        user_id: q.from.id,
+                        chat_id: linked_chat_id,
+                        workspace,
+                    })
+                    .await?;
+
+                // Prompt for account ID, as a reply since bots with privacy mode only receive
+                // replies in groups, such as the admin chats
+                bot.send_message(chat_id, "Please enter the account ID to link this chat to:")
+                    .reply_markup(ForceReply::new())
+                    .await?;
+            }
+            CallbackData::Defaults {
+                chat_id: tg_chat_id,
+            } => show_chat_defaults(&bot, chat_id, &tg_chat_id, &settings).await?,
+            CallbackData::EditDefault {
+                chat_id: tg_chat_id,
+                field,
+            } => {
+                dialogue
+                    .update(State::WaitingForChatDefault {
+                        user_id: q.from.id,
+                        chat_id: tg_chat_id,
+                        field,
+                    })
+                    .await?;
+
+                bot.send_message(chat_id, field.prompt()).await?;
+            }
+            CallbackData::Rules {
+                chat_id: tg_chat_id,
+            } => show_chat_rules(&bot, chat_id, &tg_chat_id, &settings).await?,
+            CallbackData::AddRule {
+                chat_id: tg_chat_id,
+            } => {
+                dialogue
+                    .update(State::WaitingForRule {
+                        user_id: q.from.id,
+                        chat_id: tg_chat_id,
+                    })
+                    .await?;
+
+                bot.send_message(chat_id, RULE_PROMPT).await?;
+            }
+            CallbackData::DeleteRule {
+                chat_id: tg_chat_id,
+                index,
+            } => {
+                delete_rule(
+                    &bot,
+                    chat_id,
+                    &q.from,
+                    &tg_chat_id,
+                    index,
+                    &config,
+                    &storage,
+                )
+                .await?
+            }
+            CallbackData::CreateIssue { message_id } => {
+                bot.delete_message(chat_id, message.id()).await?;
+
+                if let Some(source) = cache.get(chat_id, MessageId(message_id)) {
+                    let args = IssueArgs {
+                        title: title_from_message(&source),
+                        requester: Some(q.from.id),
+                        topic: topic_name(&source, &cache),
+                        ..IssueArgs::default()
+                    };
+
+                    submit_issue(
+                        &bot,
+                        &source,
+                        args,
+                        message_text(&source).unwrap_or_default(),
+                        &workspaces,
+                        config,
+                        storage,
+                    )
+                    .await?;
+                } else {
+                    send_to_topic(
+                        &bot,
+                        chat_id,
+                        topic,
+                        "⚠️ Can't create an issue from this message, please reply to it with /issue",
+                    )
+                    .await?;
+                }
+            }
+            CallbackData::Dismiss => {
+                bot.delete_message(chat_id, message.id()).await?;
+            }
+            CallbackData::CloseIssue { number } => {
+                close_issue_button(
+                    &bot,
+                    chat_id,
+                    topic,
+                    &q.from,
+                    number,
+                    &workspaces,
+                    config,
+                    storage,
+                )
+                .await?
+            }
+            CallbackData::CommentIssue { number } => {
+                comment_issue_button(
+                    &bot,
+                    chat_id,
+                    topic,
+                    &q.from,
+                    number,
+                    dialogue,
+                    &workspaces,
+                    config,
+                )
+                .await?
+            }
+            CallbackData::Forward {
+                chat_id: target_chat_id,
+            } => {
+                bot.delete_message(chat_id, message.id()).await?;
+
+                forward_to_chat(
+                    &bot,
+                    &q.from,
+                    ChatId(target_chat_id),
+                    &workspaces,
+                    &settings,
+                    &storage,
+                    &forwarded,
+                )
+                .await?
+            }
+            CallbackData::Issues { page } => {
+                list_open_issues(
+                    &bot,
+                    chat_id,
+                    topic,
+                    page,
+                    Some(message.id()),
+                    &workspaces,
+                    config,
+                    &issue_lists,
+                )
+                .await?
+            }
+        }
+
+        eyre::Ok(())
+    }
+    .await
+    .wrap_err(context)
+}
+
+pub async fn handle_account_id_input(
+    bot: Bot,
+    message: Message,
+    dialogue: LinkToPylonAccountDialogue,
+    (_, chat_id, workspace): (UserId, String, Option<String>),
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    if let Some(account_id) = message.text() {
+        let account_id = account_id.trim().to_string();
+        let mut settings = config.get().await;
+
+        // The admin could have lost their rights since they started the dialogue
+        if !is_bot_admin(message.from.as_ref(), &settings) {
+            warn!("Unauthorized answer to the account id prompt");
+            dialogue.update(State::Start).await?;
+            return Ok(());
+        }
+        let workspace = workspace.unwrap_or_else(|| settings.workspace(&chat_id));
+        let pylon_client = workspaces.get(&workspace)?;
+
+        if let Some(account) = pylon_client.get_account(&account_id).await? {
+            settings.link(&chat_id, &workspace, &account_id);
+
+            config.save(settings)?;
+
+            storage
+                .audit_log()
+                .record(
+                    AuditEntry::new(
+                        message.from.as_ref().map(|user| user.id.0),
+                        AuditAction::Link,
+                        &chat_id,
+                    )
+                    .details(format!("account {account_id} in workspace {workspace}")),
+                )
+                .await?;
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "✅ Chat linked to Pylon account '{}'",
+                    account.name.clone().unwrap_or_default(),
+                ),
+            )
+            .await?;
+
+            // Reset dialogue to start
+            dialogue.update(State::Start).await?;
+
+            info!(
+                "Chat '{chat_id}' linked to Pylon Account '{}'",
+                account.name.unwrap_or_default()
+            );
+        } else {
+            bot.send_message(message.chat.id, "⚠️ Account not found in Pylon")
+                .await?;
+
+            // Reset dialogue to start
+            dialogue.update(State::Start).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_bot_status_change(
+    message: Message,
+    identity: BotIdentity,
+    config: Arc<Config>,
+) -> eyre::Result<()> {
+    match message.kind {
+        MessageKind::NewChatMembers(members)
+            if members
+                .new_chat_members
+                .iter()
+                .any(|m| m.username.as_ref() == Some(&identity.username)) =>
+        {
+            let mut settings = config.get().await;
+
+            settings.add_chat(&message.chat.id.to_string(), &identity.name);
+
+            config.save(settings)?;
+
+            info!(
+                "Bot {} was added to chat: {}, {}",
+                identity.name,
+                message.chat.id,
+                message.chat.title().unwrap_or_default()
+            );
+        }
+        MessageKind::LeftChatMember(member)
+            if member.left_chat_member.username.as_ref() == Some(&identity.username) =>
+        {
+            warn!(
+                "Bot {} was removed from chat: {}, {}",
+                identity.name,
+                message.chat.id,
+                message.chat.title().unwrap_or_default()
+            )
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn new_issue(
+    args: String,
+    bot: &Bot,
+    message: Message,
+    dialogue: NewIssueDialogue,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+) -> eyre::Result<()> {
+    let (first_line, details) = split_title(&args);
+    let mut issue_args = IssueArgs::parse(first_line);
+    issue_args.requester = message.from.as_ref().map(|user| user.id);
+    issue_args.topic = topic_name(&message, &cache);
+
+    if issue_args.force && !is_bot_admin(message.from.as_ref(), &config.get().await) {
+        reply_in_topic(
+            bot,
+            &message,
+            format!("⚠️ Only bot admins can use {FORCE_FLAG}"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(replied) = replied_message(&message) {
+        if issue_args.title.is_empty() {
+            let username = message
+                .from
+                .clone()
+                .and_then(|u| u.username)
+                .unwrap_or_default();
+            let chat_title = message.chat.title().unwrap_or_default();
+
+            issue_args.title = format!("New issue from {username} on {chat_title}");
+        }
+
+        let delete_command = config
+            .get()
+            .await
+            .chat_settings(&message.chat.id.to_string())
+            .delete_commands;
+
+        submit_issue(
+            bot,
+            replied,
+            issue_args,
+            message_text(replied).unwrap_or_default(),
+            workspaces,
+            config,
+            storage,
+        )
+        .await?;
+
+        // The confirmation replies to the message, so the command is only noise. Deleting
+        // messages of others requires the bot to be an admin of the chat.
+        if delete_command && let Err(err) = bot.delete_message(message.chat.id, message.id).await {
+            warn!(
+                "Failed to delete /issue in {}: {err}",
+                message.chat.title().unwrap_or_default()
+            );
+        }
+    } else if !issue_args.title.is_empty() {
+        submit_issue(
+            bot, &message, issue_args, details, workspaces, config, storage,
+        )
+        .await?;
+    } else if !first_line.is_empty() {
+        reply_in_topic(bot, &message, "⚠️ Please give the issue a title").await?;
+    } else if let Some(user) = &message.from {
+        if is_answering_other_user(&dialogue, user.id).await? {
+            reply_in_topic(
+                bot,
+                &message,
+                "⚠️ Someone else is answering a prompt, please give the title: /issue <title>",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        dialogue
+            .update(State::WaitingForIssueTitle { user_id: user.id })
+            .await?;
+
+        reply_in_topic(bot, &message, "Please enter the issue title:").await?;
+    }
+
+    Ok(())
+}
+
+pub async fn handle_issue_title_input(
+    bot: Bot,
+    message: Message,
+    dialogue: NewIssueDialogue,
+    user_id: UserId,
+) -> eyre::Result<()> {
+    let Some(title) = message.text().map(str::trim).filter(|t| !t.is_empty()) else {
+        reply_in_topic(&bot, &message, "⚠️ Please enter a text title:").await?;
+        return Ok(());
+    };
+
+    if title.starts_with('/') {
+        dialogue.update(State::Start).await?;
+        reply_in_topic(&bot, &message, "Issue creation cancelled").await?;
+        return Ok(());
+    }
+
+    dialogue
+        .update(State::WaitingForIssueDescription {
+            user_id,
+            title: title.to_string(),
+        })
+        .await?;
+
+    reply_in_topic(
+        &bot,
+        &message,
+        "Please describe the issue (text, photo or file):",
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_issue_description_input(
+    bot: Bot,
+    message: Message,
+    dialogue: NewIssueDialogue,
+    (_, title): (UserId, String),
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+) -> eyre::Result<()> {
+    if message_text(&message).is_some_and(|text| text.starts_with('/')) {
+        dialogue.update(State::Start).await?;
+        reply_in_topic(&bot, &message, "Issue creation cancelled").await?;
+        return Ok(());
+    }
+
+    // Reset dialogue to start
+    dialogue.update(State::Start).await?;
+
+    // Titles typed in the dialogue are kept as is, options are only given with `/issue`
+    let issue_args = IssueArgs {
+        title,
+        requester: message.from.as_ref().map(|user| user.id),
+        topic: topic_name(&message, &cache),
+        ..IssueArgs::default()
+    };
+
+    submit_issue(
+        &bot,
+        &message,
+        issue_args,
+        message_text(&message).unwrap_or_default(),
+        &workspaces,
+        config,
+        storage,
+    )
+    .await
+}
+
+/// Creates a Pylon issue whose body is `body` followed by the media attached to `source`.
+///
+/// If an issue was already created from `source`, its link is sent instead unless `force` is set.
+async fn submit_issue(
+    bot: &Bot,
+    source: &Message,
+    args: IssueArgs,
+    body: &str,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+    let chat_title = source.chat.title().unwrap_or_default();
+    let topic_id = topic_id(source).map(|topic_id| topic_id.0.0);
+    let attachment = message_attachment(source);
+
+    if body.trim().is_empty() && attachment.is_none() {
+        debug!("Nothing to create an issue from in {chat_title}");
+        return Ok(());
+    }
+
+    debug!(
+        "New issue '{}' in {chat_title}: {}",
+        Redacted(&args.title),
+        Redacted(body)
+    );
+
+    if settings
+        .topic_account(&source.chat.id.to_string(), topic_id)
+        .is_none()
+    {
+        warn!("No Pylon account defined for chat {chat_title}");
+        return Ok(());
+    }
+
+    if !args.force
+        && let Some(issue) = storage
+            .issue_for_message(source.chat.id.0, source.id.0)
+            .await
+    {
+        let quiet = settings.chat_settings(&source.chat.id.to_string()).quiet;
+
+        confirm_issue(
+            bot,
+            source,
+            args.requester.filter(|_| quiet),
+            format!(
+                "ℹ️ This message is already tracked in issue [\\#{}]({})",
+                issue.number, issue.link
+            ),
+            None,
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let pylon_client = workspaces.for_chat(&settings, &source.chat.id.to_string())?;
+
+    let options = match args.resolve(&pylon_client).await? {
+        Ok(options) => options,
+        Err(errors) => {
+            reply_in_topic(bot, source, format!("⚠️ {}", errors.join("\n"))).await?;
+            return Ok(());
+        }
+    };
+
+    let issue = create_issue(
+        bot,
+        IssueSource {
+            chat_id: source.chat.id,
+            chat_title,
+            topic_id,
+            message_id: Some(source.id),
+            author: display_name(source.from.as_ref()),
+            body,
+            attachments: attachment.into_iter().collect(),
+        },
+        &args,
+        options,
+        &pylon_client,
+        &settings,
+        &storage,
+    )
+    .await?;
+    let quiet = settings.chat_settings(&source.chat.id.to_string()).quiet;
+
+    confirm_issue(
+        bot,
+        source,
+        args.requester.filter(|_| quiet),
+        format!(
+            "✅ New issue [\\#{}]({}) created in Pylon",
+            issue.number, issue.link
+        ),
+        Some(issue_keyboard(issue.number)),
+    )
+    .await
+}
+
+/// Messages an issue is created from, and the chat whose Pylon account and defaults apply.
+struct IssueSource<'a> {
+    chat_id: ChatId,
+    chat_title: &'a str,
+    topic_id: Option<i32>,
+    /// Message the issue is tracked for, none for messages forwarded privately.
+    message_id: Option<MessageId>,
+    author: String,
+    body: &'a str,
+    attachments: Vec<(FileId, String)>,
+}
+
+/// Creates the Pylon issue of `source` with the defaults of its chat, and records it.
+async fn create_issue(
+    bot: &Bot,
+    source: IssueSource<'_>,
+    args: &IssueArgs,
+    options: IssueOptions,
+    pylon_client: &PylonClient,
+    settings: &Settings,
+    storage: &Storage,
+) -> eyre::Result<IssueRecord> {
+    let chat_id = source.chat_id.to_string();
+    let pylon_account = settings
+        .topic_account(&chat_id, source.topic_id)
+        .ok_or_eyre(format!(
+            "Chat {} is not linked to a Pylon account",
+            source.chat_title
+        ))?;
+    let mut attachment_urls = Vec::new();
+
+    for (file_id, file_name) in source.attachments {
+        let file = bot.get_file(file_id).await?;
+        let mut content = Vec::new();
+        bot.download_file(&file.path, &mut content).await?;
+
+        let response = pylon_client.create_attachment(&file_name, content).await?;
+        attachment_urls.extend(response.url);
+    }
+
+    let defaults = settings.chat_settings(&chat_id).defaults;
+    let body = if source.body.trim().is_empty() {
+        &args.title
+    } else {
+        source.body
+    };
+    let topic_name = args.topic.as_deref().unwrap_or_default();
+    let body_html =
+        text_to_html(&defaults.body(body, &source.author, source.chat_title, topic_name));
+
+    let title = defaults.title(&args.title);
+    let mut tags = defaults.tags;
+    let topic_tags = settings
+        .topic_settings(&chat_id, source.topic_id)
+        .map(|topic| topic.tags.clone())
+        .unwrap_or_default();
+    for tag in topic_tags.into_iter().chain(options.tags) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    let response = pylon_client
+        .create_issue(&Issue {
+            account_id: pylon_account.to_string(),
+            title: title.clone(),
+            body_html,
+            priority: options.priority.or(defaults.priority),
+            tags,
+            assignee_id: options.assignee_id.or(defaults.assignee_id),
+            team_id: defaults.team_id,
+            custom_fields: defaults
+                .custom_fields
+                .into_iter()
+                .map(|(slug, value)| CustomFieldValue { slug, value })
+                .collect(),
+            attachment_urls,
+        })
+        .await?;
+
+    metrics::ISSUES_CREATED.inc();
+
+    let issue = IssueRecord {
+        id: response.id.clone().unwrap_or_default(),
+        number: response.number.unwrap_or_default(),
+        title,
+        link: response.link.clone().unwrap_or_default(),
+        chat_id: source.chat_id.0,
+        topic_id: source.topic_id,
+        message_id: source
+            .message_id
+            .map(|message_id| message_id.0)
+            .unwrap_or_default(),
+        created_at: Utc::now(),
+        state: Some(response.state.clone().unwrap_or_else(|| "new".to_string())),
+        subscribers: BTreeSet::new(),
+    };
+
+    if response.id.is_some() {
+        storage.insert_issue(issue.clone()).await?;
+    }
+
+    info!("Issue #{} created in {}", issue.number, source.chat_title);
+
+    storage
+        .audit_log()
+        .record(
+            AuditEntry::new(
+                args.requester.map(|user_id| user_id.0),
+                AuditAction::IssueCreate,
+                format!("#{}", issue.number),
+            )
+            .details(match topic_name {
+                "" => format!("chat {}", source.chat_id),
+                topic_name => format!("chat {}, topic {topic_name}", source.chat_id),
+            }),
+        )
+        .await?;
+
+    Ok(issue)
+}
+
+/// Replies to `source` with `text`, a MarkdownV2 message about its issue.
+///
+/// In quiet chats, `private_to` is set to the requester, who receives the message privately
+/// instead. It is still sent to the chat if the requester never started a private chat with the
+/// bot.
+async fn confirm_issue(
+    bot: &Bot,
+    source: &Message,
+    private_to: Option<UserId>,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> eyre::Result<()> {
+    if let Some(user_id) = private_to {
+        // The buttons act on the chat they are in, so they are not sent privately
+        match bot
+            .send_message(
+                user_id,
+                format!(
+                    "{text}\nChat: {}",
+                    escape_markdown_v2(source.chat.title().unwrap_or_default())
+                ),
+            )
+            .parse_mode(ParseMode::MarkdownV2)
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(err) => warn!("Failed to send the issue confirmation to {user_id}: {err}"),
+        }
+    }
+
+    let mut request = reply_in_topic(bot, source, text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .reply_parameters(ReplyParameters::new(source.id).allow_sending_without_reply());
+
+    if let Some(keyboard) = keyboard {
+        request = request.reply_markup(keyboard);
+    }
+
+    request.await?;
+
+    Ok(())
+}
+
+async fn active(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    workspace: &str,
+    workspaces: Arc<PylonWorkspaces>,
+    settings: Settings,
+) -> eyre::Result<()> {
+    let mut count: usize = 0;
+
+    bot.send_chat_action(chat_id, ChatAction::Typing).await?;
+
+    for (tg_chat_id, pylon_account_id) in settings.bot_chats(bot_name) {
+        let chat_workspace = settings.workspace(tg_chat_id);
+
+        if pylon_account_id.is_empty() || !workspace.is_empty() && chat_workspace != workspace {
+            continue;
+        }
+
+        let chat = bot.get_chat(tg_chat_id.clone()).await?;
+        let chat_title = escape_markdown_v2(chat.title().unwrap_or_default());
+        let pylon_client = workspaces.get(&chat_workspace)?;
+
+        if let Some(pylon_account) = pylon_client.get_account(pylon_account_id).await? {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "{chat_title} ➡️ {} {}\n",
+                    escape_markdown_v2(pylon_account.name.unwrap_or_default().as_str()),
+                    escape_markdown_v2(&format!("({chat_workspace})"))
+                ),
+            )
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+            count += 1;
+        }
+    }
+
+    bot.send_message(chat_id, format!("✅ Found {count} active chats"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn unlinked(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    settings: Settings,
+) -> eyre::Result<()> {
+    let mut count: usize = 0;
+
+    bot.send_chat_action(chat_id, ChatAction::Typing).await?;
+
+    for (tg_chat_id, pylon_account_id) in settings.bot_chats(bot_name) {
+        let chat = bot.get_chat(tg_chat_id.clone()).await?;
+        let chat_title = escape_markdown_v2(chat.title().unwrap_or_default());
+
+        if pylon_account_id.is_empty() {
+            bot.send_message(chat_id, chat_title)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+            count += 1;
+        }
+    }
+
+    bot.send_message(chat_id, format!("✅ Found {count} unlinked chats"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn orphans(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    settings: Settings,
+) -> eyre::Result<()> {
+    let mut count: usize = 0;
+    bot.send_chat_action(chat_id, ChatAction::Typing).await?;
+
+    for (tg_chat_id, _) in settings.bot_chats(bot_name) {
+        let chat = bot.get_chat(tg_chat_id.clone()).await?;
+
+        if !is_bot_member(bot, chat.id).await? {
+            let chat_title = escape_markdown_v2(chat.title().unwrap_or_default());
+
+            bot.send_message(chat_id, chat_title)
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+            count += 1;
+        }
+    }
+
+    bot.send_message(chat_id, format!("✅ Found {count} orphans"))
+        .parse_mode(ParseMode::MarkdownV2)
+        .await?;
+
+    Ok(())
+}
+
+async fn link_chat_to_account(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    workspace: &str,
+    workspaces: Arc<PylonWorkspaces>,
+    setting: Settings,
+) -> eyre::Result<()> {
+    let workspace = Some(workspace.to_string()).filter(|workspace| !workspace.is_empty());
+
+    if let Some(workspace) = &workspace
+        && workspaces.get(workspace).is_err()
+    {
+        bot.send_message(chat_id, format!("⚠️ Unknown Pylon workspace '{workspace}'"))
+            .await?;
+        return Ok(());
+    }
+
+    // Create inline keyboard with unlinked chats
+    let mut keyboard = Vec::new();
+    for (chat_id, pylon_account_id) in setting.bot_chats(bot_name) {
+        if pylon_account_id.trim().is_empty() {
+            let chat = bot.get_chat(chat_id.clone()).await?;
+            let chat_title = chat.title().unwrap_or_default();
+
+            keyboard.push(vec![InlineKeyboardButton::callback(
+                chat_title,
+                CallbackData::Link {
+                    chat_id: chat_id.clone(),
+                    workspace: workspace.clone(),
+                }
+                .to_string(),
+            )]);
+        }
+    }
+
+    if keyboard.is_empty() {
+        bot.send_message(chat_id, "✅ No unlinked chats found.")
+            .parse_mode(ParseMode::MarkdownV2)
+            .await?;
+        return Ok(());
+    }
+
+    let inline_keyboard = InlineKeyboardMarkup::new(keyboard);
+
+    bot.send_message(chat_id, "Please select a chat to link:")
+        .reply_markup(inline_keyboard)
+        .await?;
+
+    Ok(())
+}
+
+/// Asks to select one of the linked chats, with buttons carrying the callback data built by `data`.
+async fn select_linked_chat(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    settings: &Settings,
+    data: impl Fn(String) -> CallbackData,
+) -> eyre::Result<()> {
+    bot.send_chat_action(chat_id, ChatAction::Typing).await?;
+
+    let mut keyboard = Vec::new();
+    for (tg_chat_id, pylon_account_id) in settings.bot_chats(bot_name) {
+        if !pylon_account_id.trim().is_empty() {
+            let chat = bot.get_chat(tg_chat_id.clone()).await?;
+            let chat_title = chat.title().unwrap_or_default();
+
+            keyboard.push(vec![InlineKeyboardButton::callback(
+                chat_title,
+                data(tg_chat_id.clone()).to_string(),
+            )]);
+        }
+    }
+
+    if keyboard.is_empty() {
+        bot.send_message(chat_id, "✅ No linked chats found.")
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_message(chat_id, "Please select a chat:")
+        .reply_markup(InlineKeyboardMarkup::new(keyboard))
+        .await?;
+
+    Ok(())
+}
+
+async fn is_bot_member(bot: &Bot, chat_id: ChatId) -> eyre::Result<bool> {
+    let bot_user = bot.get_me().await?;
+    let member = bot.get_chat_member(chat_id, bot_user.id).await?;
+
+    Ok(matches!(
+        member.status(),
+        ChatMemberStatus::Member | ChatMemberStatus::Administrator | ChatMemberStatus::Owner
+    ))
+}
+
+fn escape_markdown_v2(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|'
+            | '{' | '}' | '.' | '!' => format!("\\{}", c),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// Counts a received command by name.
+fn count_command(message: &Message) {
+    metrics::COMMANDS_RECEIVED
+        .with_label_values(&[command_name(message).unwrap_or_default()])
+        .inc();
+}
+
+/// Username of `user`, or their full name if they have none.
+fn display_name(user: Option<&User>) -> String {
+    user.map(|user| user.username.clone().unwrap_or_else(|| user.full_name()))
+        .unwrap_or_default()
+}
+
+fn is_bot_admin(user: Option<&User>, settings: &Settings) -> bool {
+    user.and_then(|user| user.username.as_ref())
+        .is_some_and(|username| settings.is_admin(username))
+}
+
+/// Maximum length of the titles generated from a message.
+const GENERATED_TITLE_LENGTH: usize = 80;
+
+/// Generates an issue title from the first line of `message`.
+fn title_from_message(message: &Message) -> String {
+    let first_line = message_text(message)
+        .and_then(|text| text.lines().map(str::trim).find(|line| !line.is_empty()));
+
+    match first_line {
+        Some(line) if line.chars().count() > GENERATED_TITLE_LENGTH => {
+            let title = line
+                .chars()
+                .take(GENERATED_TITLE_LENGTH)
+                .collect::<String>();
+            format!("{}…", title.trim_end())
+        }
+        Some(line) => line.to_string(),
+        None => format!(
+            "New issue from {} on {}",
+            message
+                .from
+                .as_ref()
+                .and_then(|user| user.username.clone())
+                .unwrap_or_default(),
+            message.chat.title().unwrap_or_default()
+        ),
+    }
+}
+
+/// Splits `/issue` arguments into the title (first line) and the details (remaining lines).
+fn split_title(args: &str) -> (&str, &str) {
+    let (title, details) = args.trim().split_once('\n').unwrap_or((args.trim(), ""));
+
+    (title.trim(), details.trim())
+}
+
+fn message_text(message: &Message) -> Option<&str> {
+    message.text().or_else(|| message.caption())
+}
+
+/// Returns the file attached to `message`, if any, along with a file name for it.
+fn message_attachment(message: &Message) -> Option<(FileId, String)> {
+    if let Some(photo) = message.photo().and_then(|sizes| sizes.last()) {
+        Some((
+            photo.file.id.clone(),
+            format!("{}.jpg", photo.file.unique_id),
+        ))
+    } else if let Some(document) = message.document() {
+        Some((
+            document.file.id.clone(),
+            document
+                .file_name
+                .clone()
+                .unwrap_or_else(|| document.file.unique_id.to_string()),
+        ))
+    } else if let Some(video) = message.video() {
+        Some((
+            video.file.id.clone(),
+            video
+                .file_name
+                .clone()
+                .unwrap_or_else(|| format!("{}.mp4", video.file.unique_id)),
+        ))
+    } else if let Some(animation) = message.animation() {
+        Some((
+            animation.file.id.clone(),
+            animation
+                .file_name
+                .clone()
+                .unwrap_or_else(|| format!("{}.mp4", animation.file.unique_id)),
+        ))
+    } else if let Some(audio) = message.audio() {
+        Some((
+            audio.file.id.clone(),
+            audio
+                .file_name
+                .clone()
+                .unwrap_or_else(|| format!("{}.mp3", audio.file.unique_id)),
+        ))
+    } else {
+        message.voice().map(|voice| {
+            (
+                voice.file.id.clone(),
+                format!("{}.ogg", voice.file.unique_id),
+            )
+        })
+    }
+}
+
+fn text_to_html(text: &str) -> String {
+    text.lines()
+        .map(html::escape)
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+pub fn is_private_chat(msg: Message) -> bool {
+    matches!(msg.chat.kind, ChatKind::Private(_))
+}
+
+pub fn is_public_chat(msg: Message) -> bool {
+    matches!(msg.chat.kind, ChatKind::Public(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{Router, body::Bytes, http::Uri};
+    use serde_json::{Value, json};
+    use teloxide::{
+        Bot,
+        dispatching::dialogue::InMemStorage,
+        prelude::Dialogue,
+        types::{ChatId, FileId, Message, UserId},
+    };
+
+    use super::{
+        Command, State, is_answering_other_user, message_attachment, process_command, split_title,
+        text_to_html, title_from_message,
+    };
+    use crate::{
+        config::Config,
+        endpoints::{IssueLists, MessageCache},
+        pylon::PylonWorkspaces,
+        storage::Storage,
+    };
+
+    const CHAT_ID: i64 = -100123;
+
+    /// Telegram Bot API answering every method with a message, and recording the calls.
+    async fn telegram_stub() -> (Bot, Arc<Mutex<Vec<(String, Value)>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+
+        let app = Router::new().fallback(move |uri: Uri, body: Bytes| {
+            let recorded = recorded.clone();
+
+            async move {
+                let method = uri
+                    .path()
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+                let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                recorded.lock().unwrap().push((method, body));
+
+                axum::Json(json!({
+                    "ok": true,
+                    "result": {
+                        "message_id": 2,
+                        "date": 0,
+                        "chat": {"id": CHAT_ID, "type": "supergroup", "title": "ACME"},
+                        "text": "ok"
+                    }
+                }))
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let bot = Bot::new("123456:test").set_api_url(format!("http://{address}").parse().unwrap());
+
+        (bot, calls)
+    }
+
+    fn group_message(text: &str) -> Message {
+        serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": CHAT_ID, "type": "supergroup", "title": "ACME"},
+            "from": {"id": 42, "is_bot": false, "first_name": "Bob", "username": "bob"},
+            "text": text
+        }))
+        .unwrap()
+    }
+
+    /// Runs `cmd` in a chat that isn't linked, without any Pylon workspace.
+    async fn run_without_workspace(name: &str, cmd: Command, text: &str) -> Vec<(String, Value)> {
+        let dir = env::temp_dir().join(format!("endpoints-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let settings_path = dir.join("settings.toml");
+        fs::write(
+            &settings_path,
+            "bot_admins = [\"alice\"]\n\n[tg_chats_to_pylon_accounts]\n",
+        )
+        .unwrap();
+
+        let config =
+            Arc::new(Config::try_new(settings_path.to_str().unwrap().to_string()).unwrap());
+        let storage = Arc::new(
+            Storage::try_new(
+                dir.join("storage.toml").to_str().unwrap().to_string(),
+                dir.join("audit.jsonl").to_str().unwrap().to_string(),
+            )
+            .unwrap(),
+        );
+        let workspaces = Arc::new(PylonWorkspaces::try_new(&config.get().await, None).unwrap());
+        let (bot, calls) = telegram_stub().await;
+        let message = group_message(text);
+        let dialogue = Dialogue::new(InMemStorage::<State>::new(), message.chat.id);
+
+        process_command(
+            bot,
+            message,
+            cmd,
+            dialogue,
+            workspaces,
+            config,
+            storage,
+            MessageCache::new(),
+            IssueLists::new(),
+        )
+        .await
+        .unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        calls.lock().unwrap().clone()
+    }
+
+    fn photo_message(caption: &str) -> Message {
+        serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": CHAT_ID, "type": "supergroup", "title": "ACME"},
+            "from": {"id": 42, "is_bot": false, "first_name": "Bob", "username": "bob"},
+            "photo": [
+                {"file_id": "small", "file_unique_id": "s", "width": 90, "height": 90, "file_size": 1},
+                {"file_id": "large", "file_unique_id": "l", "width": 800, "height": 800, "file_size": 2}
+            ],
+            "caption": caption
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_split_title() {
+        assert_eq!(
+            split_title("  Login fails  \n\nSince this morning\nOn iOS \n"),
+            ("Login fails", "Since this morning\nOn iOS")
+        );
+        assert_eq!(split_title("Login fails"), ("Login fails", ""));
+        assert_eq!(split_title(""), ("", ""));
+        assert_eq!(split_title(" \n "), ("", ""));
+    }
+
+    #[test]
+    fn test_title_from_message() {
+        assert_eq!(
+            title_from_message(&group_message("\n  Login fails \nSince this morning")),
+            "Login fails"
+        );
+        assert_eq!(
+            title_from_message(&photo_message("Broken chart\nSee the screenshot")),
+            "Broken chart"
+        );
+        assert_eq!(
+            title_from_message(&photo_message("")),
+            "New issue from bob on ACME"
+        );
+
+        // Truncated on a char boundary, even for multi-byte chars
+        let title = title_from_message(&group_message(&"é".repeat(100)));
+        assert_eq!(title, format!("{}…", "é".repeat(80)));
+
+        let line = "a".repeat(80);
+        assert_eq!(title_from_message(&group_message(&line)), line);
+    }
+
+    #[test]
+    fn test_message_attachment() {
+        assert_eq!(
+            message_attachment(&photo_message("Broken chart")),
+            Some((FileId("large".to_string()), "l.jpg".to_string()))
+        );
+        assert_eq!(message_attachment(&group_message("Login fails")), None);
+    }
+
+    #[test]
+    fn test_text_to_html() {
+        assert_eq!(
+            text_to_html("<b>Login</b> & sign up\nfail"),
+            "&lt;b&gt;Login&lt;/b&gt; &amp; sign up<br>fail"
+        );
+        assert_eq!(text_to_html(""), "");
+    }
+
+    #[test]
+    fn test_account_id_prompt_is_answered_by_its_admin() {
+        let state = State::WaitingForAccountId {
+            user_id: UserId(7),
+            chat_id: CHAT_ID.to_string(),
+            workspace: None,
+        };
+
+        // The prompt is sent to the alert chat, where other members can write
+        assert!(!state.is_answered_by(&group_message("acme")));
+
+        let mut message = group_message("acme");
+        message.from.as_mut().unwrap().id = UserId(7);
+        assert!(state.is_answered_by(&message));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_of_another_user_is_kept() {
+        let dialogue = Dialogue::new(InMemStorage::<State>::new(), ChatId(CHAT_ID));
+        assert!(
+            !is_answering_other_user(&dialogue, UserId(42))
+                .await
+                .unwrap()
+        );
+
+        dialogue
+            .update(State::WaitingForComment {
+                user_id: UserId(7),
+                number: 12,
+            })
+            .await
+            .unwrap();
+
+        assert!(
+            is_answering_other_user(&dialogue, UserId(42))
+                .await
+                .unwrap()
+        );
+        assert!(!is_answering_other_user(&dialogue, UserId(7)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_help_without_workspace() {
+        let calls = run_without_workspace("help", Command::Help, "/help").await;
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, "SendMessage");
+        assert!(calls[0].1["text"].as_str().unwrap().contains("/issue"));
+    }
+
+    #[tokio::test]
+    async fn test_status_of_unlinked_chat_without_workspace() {
+        let calls =
+            run_without_workspace("status", Command::Status("12".to_string()), "/status 12").await;
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].1["text"],
+            "⚠️ This chat is not linked to a Pylon account"
+        );
+    }
+}
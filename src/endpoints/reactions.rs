@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use teloxide::{
+    Bot,
+    types::{MessageReactionUpdated, ReactionType},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::Config,
+    endpoints::{
+        issue_args::IssueArgs, message_cache::MessageCache, message_text, submit_issue,
+        title_from_message, topics::topic_name,
+    },
+    pylon::PylonWorkspaces,
+    storage::Storage,
+};
+
+/// Creates an issue from a message when the chat's trigger emoji is added to it.
+pub async fn handle_reaction(
+    bot: Bot,
+    reaction: MessageReactionUpdated,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+    let trigger = settings
+        .chat_settings(&reaction.chat.id.to_string())
+        .reaction;
+
+    let Some(emoji) = trigger.emoji else {
+        return Ok(());
+    };
+
+    let has_emoji =
+        |reactions: &[ReactionType]| reactions.iter().any(|r| r.emoji() == Some(&emoji));
+
+    if !has_emoji(&reaction.new_reaction) || has_emoji(&reaction.old_reaction) {
+        return Ok(());
+    }
+
+    let username = reaction.user().and_then(|user| user.username.as_deref());
+
+    if !trigger.allowed.allows(username, &settings) {
+        debug!(
+            "Ignoring {emoji} reaction from {} in {}",
+            username.unwrap_or_default(),
+            reaction.chat.title().unwrap_or_default()
+        );
+        return Ok(());
+    }
+
+    // Only logged, as answering each reaction to an old message would flood the chat
+    let Some(message) = cache.get(reaction.chat.id, reaction.message_id) else {
+        warn!(
+            "Reacted message {} is not in cache for {}",
+            reaction.message_id,
+            reaction.chat.title().unwrap_or_default()
+        );
+        return Ok(());
+    };
+
+    let args = IssueArgs {
+        title: title_from_message(&message),
+        requester: reaction.user().map(|user| user.id),
+        topic: topic_name(&message, &cache),
+        ..IssueArgs::default()
+    };
+
+    submit_issue(
+        &bot,
+        &message,
+        args,
+        message_text(&message).unwrap_or_default(),
+        &workspaces,
+        config,
+        storage,
+    )
+    .await
+}
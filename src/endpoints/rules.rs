@@ -0,0 +1,439 @@
+use std::sync::Arc;
+
+use regex::Regex;
+use teloxide::{
+    Bot,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{
+        ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message, ReplyParameters, User, UserId,
+    },
+};
+use tracing::{info, warn};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    config::{Config, IssueRule, RuleAction, Senders, Settings},
+    endpoints::{
+        LinkToPylonAccountDialogue, State,
+        callback::CallbackData,
+        is_bot_admin,
+        issue_args::IssueArgs,
+        message_cache::MessageCache,
+        message_text, select_linked_chat, submit_issue, title_from_message,
+        topics::{reply_in_topic, topic_name},
+    },
+    pylon::PylonWorkspaces,
+    storage::Storage,
+};
+
+/// Returns the first rule of the chat matching `message`, if the chat is linked.
+pub async fn matching_rule(message: Message, config: Arc<Config>) -> Option<IssueRule> {
+    let text = message_text(&message).filter(|text| !text.starts_with('/'))?;
+    let settings = config.get().await;
+    let chat_id = message.chat.id.to_string();
+
+    settings.pylon_account(&chat_id)?;
+
+    let username = message
+        .from
+        .as_ref()
+        .and_then(|user| user.username.as_deref());
+
+    settings
+        .chat_settings(&chat_id)
+        .rules
+        .into_iter()
+        .find(|rule| rule.matches(text, username, &settings))
+}
+
+pub async fn handle_rule_match(
+    bot: Bot,
+    message: Message,
+    rule: IssueRule,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    cache: Arc<MessageCache>,
+) -> eyre::Result<()> {
+    info!(
+        "Message in {} matches rule '{}'",
+        message.chat.title().unwrap_or_default(),
+        rule.name
+    );
+
+    match rule.action {
+        RuleAction::Create => {
+            let args = IssueArgs {
+                title: title_from_message(&message),
+                topic: topic_name(&message, &cache),
+                ..IssueArgs::default()
+            };
+
+            submit_issue(
+                &bot,
+                &message,
+                args,
+                message_text(&message).unwrap_or_default(),
+                &workspaces,
+                config,
+                storage,
+            )
+            .await?;
+        }
+        RuleAction::Confirm => {
+            if storage
+                .issue_for_message(message.chat.id.0, message.id.0)
+                .await
+                .is_some()
+            {
+                return Ok(());
+            }
+
+            let keyboard = InlineKeyboardMarkup::new([[
+                InlineKeyboardButton::callback(
+                    "🎫 Create issue",
+                    CallbackData::CreateIssue {
+                        message_id: message.id.0,
+                    }
+                    .to_string(),
+                ),
+                InlineKeyboardButton::callback("Dismiss", CallbackData::Dismiss.to_string()),
+            ]]);
+
+            reply_in_topic(
+                &bot,
+                &message,
+                format!("🚨 This looks like an issue ({})", rule.name),
+            )
+            .reply_parameters(ReplyParameters::new(message.id))
+            .reply_markup(keyboard)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the linked chats whose rules can be edited.
+pub async fn chat_rules(
+    bot: &Bot,
+    chat_id: ChatId,
+    bot_name: &str,
+    settings: Settings,
+) -> eyre::Result<()> {
+    select_linked_chat(bot, chat_id, bot_name, &settings, |chat_id| {
+        CallbackData::Rules { chat_id }
+    })
+    .await
+}
+
+/// Sends the rules of `tg_chat_id`, with buttons to add and delete rules.
+pub async fn show_chat_rules(
+    bot: &Bot,
+    chat_id: ChatId,
+    tg_chat_id: &str,
+    settings: &Settings,
+) -> eyre::Result<()> {
+    let chat = bot.get_chat(tg_chat_id.to_string()).await?;
+    let rules = settings.chat_settings(tg_chat_id).rules;
+
+    let mut keyboard = rules
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            vec![InlineKeyboardButton::callback(
+                format!("🗑 {}", rule.name),
+                CallbackData::DeleteRule {
+                    chat_id: tg_chat_id.to_string(),
+                    index,
+                }
+                .to_string(),
+            )]
+        })
+        .collect::<Vec<_>>();
+    keyboard.push(vec![InlineKeyboardButton::callback(
+        "➕ Add rule",
+        CallbackData::AddRule {
+            chat_id: tg_chat_id.to_string(),
+        }
+        .to_string(),
+    )]);
+
+    let description = if rules.is_empty() {
+        "No rules".to_string()
+    } else {
+        rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| format!("{}. {}", index + 1, describe_rule(rule)))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Rules for {}:\n\n{description}",
+            chat.title().unwrap_or_default()
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::new(keyboard))
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_rule(
+    bot: &Bot,
+    chat_id: ChatId,
+    user: &User,
+    tg_chat_id: &str,
+    index: usize,
+    config: &Config,
+    storage: &Storage,
+) -> eyre::Result<()> {
+    let mut settings = config.get().await;
+
+    if let Some(chat_settings) = settings.tg_chats_settings.get_mut(tg_chat_id)
+        && index < chat_settings.rules.len()
+    {
+        let rule = chat_settings.rules.remove(index);
+        config.save(settings.clone())?;
+
+        info!("Rule '{}' of chat '{tg_chat_id}' deleted", rule.name);
+
+        storage
+            .audit_log()
+            .record(
+                AuditEntry::new(Some(user.id.0), AuditAction::SettingsChange, tg_chat_id)
+                    .details(format!("rule '{}' deleted", rule.name)),
+            )
+            .await?;
+    }
+
+    show_chat_rules(bot, chat_id, tg_chat_id, &settings).await
+}
+
+/// Prompt describing the format of the rules sent by admins.
+pub const RULE_PROMPT: &str = "Please describe the rule, one setting per line:
+
+name: Production down
+keywords: production down, cannot prove
+pattern: (?i)rpc.*\\b5\\d\\d\\b
+senders: everyone | customers | team
+min_length: 10
+action: confirm | create
+
+A message matches if it contains one of the keywords or matches one of the patterns.";
+
+pub async fn handle_rule_input(
+    bot: Bot,
+    message: Message,
+    dialogue: LinkToPylonAccountDialogue,
+    (_, chat_id): (UserId, String),
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let Some(text) = message.text() else {
+        return Ok(());
+    };
+
+    // Reset dialogue to start
+    dialogue.update(State::Start).await?;
+
+    let mut settings = config.get().await;
+
+    // The admin could have lost their rights since they started the dialogue
+    if !is_bot_admin(message.from.as_ref(), &settings) {
+        warn!("Unauthorized answer to the rule prompt");
+        return Ok(());
+    }
+
+    let rule = match parse_rule(text) {
+        Ok(rule) => rule,
+        Err(err) => {
+            bot.send_message(message.chat.id, format!("⚠️ {err}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let rule_name = rule.name.clone();
+
+    settings
+        .tg_chats_settings
+        .entry(chat_id.clone())
+        .or_default()
+        .rules
+        .push(rule);
+
+    config.save(settings.clone())?;
+
+    info!("Rule '{rule_name}' added to chat '{chat_id}'");
+
+    storage
+        .audit_log()
+        .record(
+            AuditEntry::new(
+                message.from.as_ref().map(|user| user.id.0),
+                AuditAction::SettingsChange,
+                &chat_id,
+            )
+            .details(format!("rule '{rule_name}' added")),
+        )
+        .await?;
+
+    show_chat_rules(&bot, message.chat.id, &chat_id, &settings).await
+}
+
+fn parse_rule(text: &str) -> eyre::Result<IssueRule> {
+    let mut rule = IssueRule::default();
+
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Expected `setting: value`, got `{line}`"))?;
+        let value = value.trim();
+
+        match key.trim() {
+            "name" => rule.name = value.to_string(),
+            "keywords" => rule.keywords.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(str::to_string),
+            ),
+            "pattern" | "patterns" => {
+                Regex::new(value).map_err(|err| eyre::eyre!("Invalid pattern: {err}"))?;
+                rule.patterns.push(value.to_string());
+            }
+            "senders" => {
+                rule.senders = match value {
+                    "everyone" => Senders::Everyone,
+                    "customers" => Senders::Customers,
+                    "team" => Senders::Team,
+                    _ => eyre::bail!("Unknown senders `{value}`"),
+                }
+            }
+            "min_length" => {
+                rule.min_length = value
+                    .parse()
+                    .map_err(|_| eyre::eyre!("Invalid min_length `{value}`"))?
+            }
+            "action" => {
+                rule.action = match value {
+                    "create" => RuleAction::Create,
+                    "confirm" => RuleAction::Confirm,
+                    _ => eyre::bail!("Unknown action `{value}`"),
+                }
+            }
+            key => eyre::bail!("Unknown setting `{key}`"),
+        }
+    }
+
+    if rule.name.is_empty() {
+        eyre::bail!("The rule needs a name");
+    }
+
+    if rule.keywords.is_empty() && rule.patterns.is_empty() {
+        eyre::bail!("The rule needs at least one keyword or pattern");
+    }
+
+    rule.compile()?;
+
+    Ok(rule)
+}
+
+fn describe_rule(rule: &IssueRule) -> String {
+    let senders = match rule.senders {
+        Senders::Everyone => "everyone",
+        Senders::Customers => "customers",
+        Senders::Team => "team",
+    };
+    let action = match rule.action {
+        RuleAction::Create => "create",
+        RuleAction::Confirm => "confirm",
+    };
+
+    let mut lines = vec![format!("{} ({action})", rule.name)];
+
+    if !rule.keywords.is_empty() {
+        lines.push(format!("keywords: {}", rule.keywords.join(", ")));
+    }
+
+    for pattern in &rule.patterns {
+        lines.push(format!("pattern: {pattern}"));
+    }
+
+    lines.push(format!(
+        "senders: {senders}, min length: {}",
+        rule.min_length
+    ));
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_rule;
+    use crate::config::{RuleAction, Senders, Settings};
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = parse_rule(
+            "name: Production down
+keywords: production down, , cannot prove
+pattern: (?i)rpc.*\\b5\\d\\d\\b
+senders: team
+min_length: 10
+action: create",
+        )
+        .unwrap();
+
+        assert_eq!(rule.name, "Production down");
+        assert_eq!(rule.keywords, vec!["production down", "cannot prove"]);
+        assert_eq!(rule.patterns, vec![r"(?i)rpc.*\b5\d\d\b"]);
+        assert_eq!(rule.senders, Senders::Team);
+        assert_eq!(rule.min_length, 10);
+        assert_eq!(rule.action, RuleAction::Create);
+    }
+
+    #[test]
+    fn test_parsed_rule_matches_patterns() {
+        let rule = parse_rule("name: RPC errors\npattern: (?i)rpc.*\\b5\\d\\d\\b").unwrap();
+        let settings = Settings::default();
+
+        assert!(rule.matches("RPC answered 503", None, &settings));
+        assert!(!rule.matches("RPC answered 404", None, &settings));
+    }
+
+    #[test]
+    fn test_reject_invalid_rules() {
+        let error = |text| parse_rule(text).unwrap_err().to_string();
+
+        assert!(error("name: Broken\npattern: (unclosed").starts_with("Invalid pattern"));
+        assert_eq!(error("keywords: down"), "The rule needs a name");
+        assert_eq!(
+            error("name: Empty"),
+            "The rule needs at least one keyword or pattern"
+        );
+        assert_eq!(
+            error("name: Typo\nkeyword: down"),
+            "Unknown setting `keyword`"
+        );
+        assert_eq!(
+            error("name: Senders\nkeywords: down\nsenders: bots"),
+            "Unknown senders `bots`"
+        );
+        assert_eq!(
+            error("name: Length\nkeywords: down\nmin_length: -1"),
+            "Invalid min_length `-1`"
+        );
+        assert_eq!(
+            error("just text"),
+            "Expected `setting: value`, got `just text`"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use teloxide::{
+    Bot,
+    payloads::{EditMessageTextSetters, SendChatActionSetters, SendMessageSetters},
+    prelude::Requester,
+    types::{
+        ChatAction, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode,
+        ThreadId,
+    },
+};
+
+use crate::{
+    config::Config,
+    endpoints::{callback::CallbackData, escape_markdown_v2, topics::send_to_topic},
+    pylon::{IssueFilter, IssueResponse, OPEN_STATES, PylonClient, PylonWorkspaces},
+};
+
+/// Issues listed per page by `/issues`.
+const ISSUES_PER_PAGE: usize = 10;
+
+/// Maximum number of issues listed by `/issues`.
+const MAX_LISTED_ISSUES: usize = 200;
+
+/// Time the issues listed by `/issues` are kept for its "Previous" and "Next" buttons.
+const ISSUE_LIST_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Open issues listed by `/issues`, by message, so that changing page doesn't fetch them again.
+#[derive(Default)]
+pub struct IssueLists {
+    lists: Mutex<HashMap<(ChatId, MessageId), IssueList>>,
+}
+
+struct IssueList {
+    listed_at: Instant,
+    issues: Vec<IssueResponse>,
+}
+
+impl IssueLists {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Locks the lists, dropping the ones that were sent too long ago.
+    fn lists(&self) -> MutexGuard<'_, HashMap<(ChatId, MessageId), IssueList>> {
+        let mut lists = self.lists.lock().unwrap();
+        lists.retain(|_, list| list.listed_at.elapsed() < ISSUE_LIST_LIFETIME);
+
+        lists
+    }
+
+    fn get(&self, chat_id: ChatId, message_id: MessageId) -> Option<Vec<IssueResponse>> {
+        self.lists()
+            .get(&(chat_id, message_id))
+            .map(|list| list.issues.clone())
+    }
+
+    fn insert(&self, chat_id: ChatId, message_id: MessageId, issues: Vec<IssueResponse>) {
+        self.lists().insert(
+            (chat_id, message_id),
+            IssueList {
+                listed_at: Instant::now(),
+                issues,
+            },
+        );
+    }
+}
+
+/// Sends the status of the issue `number` of the Pylon account of the chat or forum topic.
+pub async fn issue_status(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    number: &str,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+
+    let Some(account_id) =
+        settings.topic_account(&chat_id.to_string(), topic_id.map(|topic_id| topic_id.0.0))
+    else {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            "⚠️ This chat is not linked to a Pylon account",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let pylon_client = workspaces.for_chat(&settings, &chat_id.to_string())?;
+
+    match chat_issue(&pylon_client, account_id, number).await? {
+        Some(issue) => {
+            send_to_topic(bot, chat_id, topic_id, describe_issue(&issue))
+                .parse_mode(ParseMode::MarkdownV2)
+                .await?;
+        }
+        None => {
+            send_to_topic(
+                bot,
+                chat_id,
+                topic_id,
+                format!(
+                    "⚠️ Issue {} not found",
+                    number.trim().trim_start_matches('#')
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the issue `number`, provided it belongs to `account_id`.
+///
+/// Issues of other accounts are reported as not found, so that a chat can't learn anything about
+/// the issues of another account.
+pub async fn chat_issue(
+    pylon_client: &PylonClient,
+    account_id: &str,
+    number: &str,
+) -> eyre::Result<Option<IssueResponse>> {
+    let number = number.trim().trim_start_matches('#');
+
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let issue = pylon_client
+        .get_issue(number)
+        .await?
+        .filter(|issue| issue.account_id() == Some(account_id));
+
+    Ok(issue)
+}
+
+/// Sends the page `page` of the open issues of the Pylon account of the chat or forum topic, or
+/// replaces `message_id` with it.
+#[allow(clippy::too_many_arguments)]
+pub async fn list_open_issues(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    page: usize,
+    message_id: Option<MessageId>,
+    workspaces: &PylonWorkspaces,
+    config: Arc<Config>,
+    lists: &IssueLists,
+) -> eyre::Result<()> {
+    let settings = config.get().await;
+
+    let Some(account_id) =
+        settings.topic_account(&chat_id.to_string(), topic_id.map(|topic_id| topic_id.0.0))
+    else {
+        send_to_topic(
+            bot,
+            chat_id,
+            topic_id,
+            "⚠️ This chat is not linked to a Pylon account",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let issues = match message_id.and_then(|message_id| lists.get(chat_id, message_id)) {
+        Some(issues) => issues,
+        None => {
+            let pylon_client = workspaces.for_chat(&settings, &chat_id.to_string())?;
+
+            let mut typing = bot.send_chat_action(chat_id, ChatAction::Typing);
+            if let Some(topic_id) = topic_id {
+                typing = typing.message_thread_id(topic_id);
+            }
+            typing.await?;
+
+            open_issues(&pylon_client, account_id).await?
+        }
+    };
+
+    let pages = issues.len().div_ceil(ISSUES_PER_PAGE).max(1);
+    let page = page.min(pages - 1);
+
+    let text = if issues.is_empty() {
+        "✅ No open issues".to_string()
+    } else {
+        let lines = issues
+            .iter()
+            .skip(page * ISSUES_PER_PAGE)
+            .take(ISSUES_PER_PAGE)
+            .map(|issue| {
+                format!(
+                    "• [\\#{}]({}) {} \\({}\\)",
+                    issue.number.unwrap_or_default(),
+                    issue.link.clone().unwrap_or_default(),
+                    escape_markdown_v2(issue.title.as_deref().unwrap_or_default()),
+                    escape_markdown_v2(&display_state(issue))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            "{} open issues, page {}/{pages}\n\n{lines}",
+            issues.len(),
+            page + 1
+        )
+    };
+
+    let mut buttons = Vec::new();
+
+    if page > 0 {
+        buttons.push(InlineKeyboardButton::callback(
+            "◀️ Previous",
+            CallbackData::Issues { page: page - 1 }.to_string(),
+        ));
+    }
+
+    if page + 1 < pages {
+        buttons.push(InlineKeyboardButton::callback(
+            "Next ▶️",
+            CallbackData::Issues { page: page + 1 }.to_string(),
+        ));
+    }
+
+    let keyboard = InlineKeyboardMarkup::new(if buttons.is_empty() {
+        vec![]
+    } else {
+        vec![buttons]
+    });
+
+    let message_id = match message_id {
+        Some(message_id) => {
+            bot.edit_message_text(chat_id, message_id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(keyboard)
+                .await?;
+
+            message_id
+        }
+        None => {
+            send_to_topic(bot, chat_id, topic_id, text)
+                .parse_mode(ParseMode::MarkdownV2)
+                .reply_markup(keyboard)
+                .await?
+                .id
+        }
+    };
+
+    lists.insert(chat_id, message_id, issues);
+
+    Ok(())
+}
+
+/// Gets the open issues of `account_id`, the most recent first.
+async fn open_issues(
+    pylon_client: &PylonClient,
+    account_id: &str,
+) -> eyre::Result<Vec<IssueResponse>> {
+    let filter = IssueFilter {
+        account_id: Some(account_id.to_string()),
+        states: OPEN_STATES.map(str::to_string).to_vec(),
+    };
+    let mut issues = Vec::new();
+    let mut cursor = None;
+
+    loop {
+        let (page_issues, next_cursor) = pylon_client
+            .list_issues(&filter, 100, cursor.as_deref())
+            .await?;
+        issues.extend(page_issues);
+
+        match next_cursor {
+            Some(next_cursor) if issues.len() < MAX_LISTED_ISSUES => cursor = Some(next_cursor),
+            _ => break,
+        }
+    }
+
+    issues.truncate(MAX_LISTED_ISSUES);
+    issues.sort_by_key(|issue| std::cmp::Reverse(issue.number));
+
+    Ok(issues)
+}
+
+/// Describes an issue in MarkdownV2.
+pub fn describe_issue(issue: &IssueResponse) -> String {
+    let last_update = issue
+        .latest_message_time
+        .as_deref()
+        .or(issue.created_at.as_deref())
+        .map(display_time)
+        .unwrap_or_else(|| "-".to_string());
+
+    format!(
+        "[\\#{}]({}) {}\nState: {}\nAssignee: {}\nLast update: {}",
+        issue.number.unwrap_or_default(),
+        issue.link.clone().unwrap_or_default(),
+        escape_markdown_v2(issue.title.as_deref().unwrap_or_default()),
+        escape_markdown_v2(&display_state(issue)),
+        escape_markdown_v2(
+            issue
+                .assignee
+                .as_ref()
+                .and_then(|assignee| assignee.email.as_deref())
+                .unwrap_or("-")
+        ),
+        escape_markdown_v2(&last_update)
+    )
+}
+
+pub fn display_state(issue: &IssueResponse) -> String {
+    issue
+        .state
+        .as_deref()
+        .unwrap_or("unknown")
+        .replace('_', " ")
+}
+
+fn display_time(time: &str) -> String {
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| {
+            time.with_timezone(&Utc)
+                .format("%Y-%m-%d %H:%M UTC")
+                .to_string()
+        })
+        .unwrap_or_else(|_| time.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::display_time;
+
+    #[test]
+    fn test_display_time_in_utc() {
+        assert_eq!(
+            display_time("2025-03-01T09:30:00+02:00"),
+            "2025-03-01 07:30 UTC"
+        );
+        assert_eq!(
+            display_time("2025-03-01T23:30:00-01:00"),
+            "2025-03-02 00:30 UTC"
+        );
+        assert_eq!(display_time("yesterday"), "yesterday");
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use teloxide::{Bot, prelude::Requester, types::Message};
+use tracing::info;
+
+use crate::{endpoints::topics::reply_in_topic, storage::Storage};
+
+/// Handles `/subscribe <number>` and `/unsubscribe <number>`.
+pub async fn set_subscription(
+    bot: &Bot,
+    message: &Message,
+    number: &str,
+    subscribed: bool,
+    storage: Arc<Storage>,
+) -> eyre::Result<()> {
+    let Some(user) = &message.from else {
+        return Ok(());
+    };
+
+    let number = number.trim().trim_start_matches('#');
+    let issue = match number.parse() {
+        Ok(number) => storage.chat_issue(message.chat.id.0, number).await,
+        Err(_) => None,
+    };
+
+    let Some(issue) = issue else {
+        reply_in_topic(
+            bot,
+            message,
+            format!("⚠️ Issue {number} was not created from this chat"),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    if subscribed {
+        // Users must have started a private chat with the bot to receive its messages
+        if bot
+            .send_message(
+                user.id,
+                format!("🔔 You will be notified of the updates of issue #{number}"),
+            )
+            .await
+            .is_err()
+        {
+            reply_in_topic(
+                bot,
+                message,
+                "⚠️ Please start a private chat with me first, so that I can notify you",
+            )
+            .await?;
+            return Ok(());
+        }
+    } else {
+        reply_in_topic(
+            bot,
+            message,
+            format!("🔕 You won't be notified of the updates of issue #{number} anymore"),
+        )
+        .await?;
+    }
+
+    storage
+        .set_subscription(&issue.id, user.id.0, subscribed)
+        .await?;
+
+    info!(
+        "User {} {} issue #{number}",
+        user.id,
+        if subscribed {
+            "subscribed to"
+        } else {
+            "unsubscribed from"
+        }
+    );
+
+    Ok(())
+}
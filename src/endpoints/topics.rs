@@ -0,0 +1,61 @@
+use teloxide::{
+    Bot,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, Message, ThreadId},
+};
+
+use crate::endpoints::MessageCache;
+
+/// Forum topic of `message`, none outside of forum supergroups.
+pub fn topic_id(message: &Message) -> Option<ThreadId> {
+    message.thread_id.filter(|_| message.is_topic_message)
+}
+
+/// Message `message` replies to.
+///
+/// In forum topics, the messages that don't reply to another one reply to the creation of their
+/// topic, which is ignored.
+pub fn replied_message(message: &Message) -> Option<&Message> {
+    message
+        .reply_to_message()
+        .filter(|replied| replied.forum_topic_created().is_none())
+}
+
+/// Name of the forum topic of `message`, if known.
+///
+/// Telegram only sends topic names with the creation and edition of topics, which are kept by
+/// the [`MessageCache`].
+pub fn topic_name(message: &Message, cache: &MessageCache) -> Option<String> {
+    let topic_id = topic_id(message)?;
+
+    message
+        .reply_to_message()
+        .and_then(|replied| replied.forum_topic_created())
+        .map(|topic| topic.name.clone())
+        .or_else(|| cache.topic_name(message.chat.id, topic_id))
+}
+
+/// Sends `text` to `chat_id`, in the forum topic `topic_id` if set.
+pub fn send_to_topic(
+    bot: &Bot,
+    chat_id: ChatId,
+    topic_id: Option<ThreadId>,
+    text: impl Into<String>,
+) -> <Bot as Requester>::SendMessage {
+    let request = bot.send_message(chat_id, text);
+
+    match topic_id {
+        Some(topic_id) => request.message_thread_id(topic_id),
+        None => request,
+    }
+}
+
+/// Sends `text` to the chat of `message`, in the same forum topic.
+pub fn reply_in_topic(
+    bot: &Bot,
+    message: &Message,
+    text: impl Into<String>,
+) -> <Bot as Requester>::SendMessage {
+    send_to_topic(bot, message.chat.id, topic_id(message), text)
+}
@@ -1,2 +1,6 @@
+pub mod audit;
 pub mod config;
+pub mod metrics;
 pub mod pylon;
+pub mod secret;
+pub mod storage;
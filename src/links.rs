@@ -0,0 +1,198 @@
+use std::{sync::Arc, time::Duration};
+
+use teloxide::{
+    RequestError,
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup},
+};
+use tokio::{select, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{
+    bots::Bots,
+    config::{Config, Settings},
+    endpoints::CallbackData,
+    pylon::PylonWorkspaces,
+};
+
+/// Link between a chat and a Pylon account that doesn't work anymore.
+struct BrokenLink {
+    chat_id: String,
+    chat_title: Option<String>,
+    problem: LinkProblem,
+}
+
+enum LinkProblem {
+    /// The account was deleted or merged in Pylon.
+    AccountNotFound {
+        workspace: String,
+        account_id: String,
+    },
+    /// The bot can't access the chat, for example because it was removed from it.
+    ChatUnreachable(String),
+}
+
+/// Checks at startup and on a schedule that the linked chats and Pylon accounts still exist, and
+/// sends the broken links to the admin chats with buttons to link them again.
+pub struct LinkChecker {
+    bots: Arc<Bots>,
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+}
+
+impl LinkChecker {
+    pub fn new(bots: Arc<Bots>, workspaces: Arc<PylonWorkspaces>, config: Arc<Config>) -> Self {
+        Self {
+            bots,
+            workspaces,
+            config,
+        }
+    }
+
+    pub async fn run(self, token: CancellationToken) {
+        let settings = self.config.get().await;
+
+        if settings.link_check.enabled && settings.alerts.chat_ids.is_empty() {
+            warn!("Link check is enabled but no alert chat is set, broken links are only logged");
+        }
+
+        loop {
+            // Settings are read on each check to pick up reloads
+            let settings = self.config.get().await;
+
+            if settings.link_check.enabled {
+                self.check(&settings).await;
+            }
+
+            select! {
+                _ = token.cancelled() => {
+                    break;
+                }
+                _ = sleep(Duration::from_secs(settings.link_check.interval_hours.max(1) * 3600)) => {}
+            }
+        }
+    }
+
+    async fn check(&self, settings: &Settings) {
+        let mut chats = settings
+            .tg_chats_to_pylon_accounts
+            .iter()
+            .filter(|(_, account_id)| !account_id.is_empty())
+            .collect::<Vec<_>>();
+        chats.sort();
+
+        let mut broken = Vec::new();
+
+        for (chat_id, account_id) in chats {
+            let bot = match self.bots.for_chat(settings, chat_id) {
+                Ok(bot) => bot,
+                Err(err) => {
+                    warn!("Failed to check chat {chat_id}: {err}");
+                    continue;
+                }
+            };
+            let chat_title = match bot.get_chat(chat_id.clone()).await {
+                Ok(chat) => chat.title().map(str::to_string),
+                Err(RequestError::Api(err)) => {
+                    broken.push(BrokenLink {
+                        chat_id: chat_id.clone(),
+                        chat_title: None,
+                        problem: LinkProblem::ChatUnreachable(err.to_string()),
+                    });
+                    continue;
+                }
+                Err(err) => {
+                    warn!("Failed to check chat {chat_id}: {err}");
+                    None
+                }
+            };
+
+            let workspace = settings.workspace(chat_id);
+            let pylon_client = match self.workspaces.get(&workspace) {
+                Ok(pylon_client) => pylon_client,
+                Err(err) => {
+                    warn!("Failed to check chat {chat_id}: {err}");
+                    continue;
+                }
+            };
+
+            match pylon_client.get_account(account_id).await {
+                Ok(Some(_)) => {}
+                Ok(None) => broken.push(BrokenLink {
+                    chat_id: chat_id.clone(),
+                    chat_title,
+                    problem: LinkProblem::AccountNotFound {
+                        workspace,
+                        account_id: account_id.clone(),
+                    },
+                }),
+                Err(err) => warn!("Failed to check Pylon account {account_id}: {err}"),
+            }
+        }
+
+        info!("Checked linked chats, {} broken", broken.len());
+
+        if !broken.is_empty() {
+            self.report(&broken, settings).await;
+        }
+    }
+
+    async fn report(&self, broken: &[BrokenLink], settings: &Settings) {
+        let mut text = format!("🔗 {} broken chat link(s):", broken.len());
+        let mut keyboard = Vec::new();
+
+        for link in broken {
+            let chat = match &link.chat_title {
+                Some(title) => format!("{title} ({})", link.chat_id),
+                None => link.chat_id.clone(),
+            };
+
+            match &link.problem {
+                LinkProblem::AccountNotFound {
+                    workspace,
+                    account_id,
+                } => {
+                    warn!(
+                        "Broken link of chat {}: Pylon account {account_id} not found in workspace {workspace}",
+                        link.chat_id
+                    );
+                    text.push_str(&format!(
+                        "\n{chat}: Pylon account {account_id} not found in workspace {workspace}"
+                    ));
+                    keyboard.push(vec![InlineKeyboardButton::callback(
+                        format!("Relink {chat}"),
+                        CallbackData::Link {
+                            chat_id: link.chat_id.clone(),
+                            workspace: Some(workspace.clone()),
+                        }
+                        .to_string(),
+                    )]);
+                }
+                LinkProblem::ChatUnreachable(err) => {
+                    warn!(
+                        "Broken link of chat {}: chat not reachable ({err})",
+                        link.chat_id
+                    );
+                    text.push_str(&format!("\n{chat}: chat not reachable ({err})"));
+                }
+            }
+        }
+
+        for chat_id in &settings.alerts.chat_ids {
+            let mut request = self
+                .bots
+                .default_bot()
+                .send_message(ChatId(*chat_id), &text);
+
+            if !keyboard.is_empty() {
+                request = request.reply_markup(InlineKeyboardMarkup::new(keyboard.clone()));
+            }
+
+            if let Err(err) = request.await {
+                warn!("Failed to send broken links to {chat_id}: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+use rolling_file::{BasicRollingFileAppender, RollingConditionBasic};
+use teloxide::{
+    dispatching::UpdateHandler,
+    dptree::{self, HandlerSignature},
+    types::{Update, UpdateKind},
+};
+use tracing::{Instrument, field::Empty, info_span, level_filters::LevelFilter};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    fmt::{MakeWriter, layer},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+
+use crate::{alerts::command_name, cli::Args, secret};
+
+/// Whether message texts are written to the logs.
+static LOG_MESSAGE_TEXT: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum LogRotation {
+    #[default]
+    Daily,
+    Hourly,
+    Never,
+}
+
+/// Sets up logging to stdout and, if `logs_path` is set, to rotated files.
+///
+/// The returned guard flushes the files when dropped.
+pub fn init(args: &Args) -> eyre::Result<Option<WorkerGuard>> {
+    LOG_MESSAGE_TEXT.store(args.log_message_text, Ordering::Relaxed);
+
+    let mut layers = vec![
+        format_layer(args.log_format, std::io::stdout, true)
+            .with_filter(
+                EnvFilter::builder()
+                    .with_default_directive(LevelFilter::INFO.into())
+                    .from_env_lossy(),
+            )
+            .boxed(),
+    ];
+    let mut guard = None;
+
+    if let Some(logs_path) = &args.logs_path {
+        let mut condition = match args.log_rotation {
+            LogRotation::Daily => RollingConditionBasic::new().daily(),
+            LogRotation::Hourly => RollingConditionBasic::new().hourly(),
+            LogRotation::Never => RollingConditionBasic::new(),
+        };
+
+        if let Some(max_size) = args.log_max_size_mb {
+            condition = condition.max_size(max_size * 1024 * 1024);
+        }
+
+        let file_appender = BasicRollingFileAppender::new(
+            Path::new(logs_path).join("logs.txt"),
+            condition,
+            args.log_max_files,
+        )?;
+        let (non_blocking, file_guard) = tracing_appender::non_blocking(file_appender);
+
+        layers.push(
+            format_layer(args.log_format, non_blocking, false)
+                .with_filter(args.log_file_level)
+                .boxed(),
+        );
+        guard = Some(file_guard);
+    }
+
+    tracing_subscriber::registry().with(layers).init();
+
+    Ok(guard)
+}
+
+fn format_layer<W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Text => layer()
+            .compact()
+            .with_target(false)
+            .with_ansi(ansi)
+            .with_writer(Scrubbing(writer))
+            .boxed(),
+        LogFormat::Json => layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(Scrubbing(writer))
+            .boxed(),
+    }
+}
+
+/// Writer removing the tokens from the logs, in case one ends up in a message or an error.
+struct Scrubbing<W>(W);
+
+impl<'a, W: MakeWriter<'a>> MakeWriter<'a> for Scrubbing<W> {
+    type Writer = ScrubbingWriter<W::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ScrubbingWriter(self.0.make_writer())
+    }
+}
+
+struct ScrubbingWriter<W>(W);
+
+impl<W: Write> Write for ScrubbingWriter<W> {
+    /// Each event is written at once, so tokens are never split between two writes.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_all(secret::scrub(&String::from_utf8_lossy(buf)).as_bytes())?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Runs the handlers of an update in a span identifying the update, its chat, user and command.
+///
+/// The Pylon request ids are added to the span by the [`PylonClient`](crate::pylon::PylonClient).
+pub fn update_span() -> UpdateHandler<eyre::Report> {
+    dptree::from_fn(
+        |deps, cont| async move {
+            let update = deps.get::<Update>();
+            let command = match &update.kind {
+                UpdateKind::Message(message) => command_name(message),
+                _ => None,
+            };
+            let span = info_span!(
+                "update",
+                update_id = update.id.0,
+                chat_id = update.chat().map(|chat| chat.id.0),
+                user_id = update.from().map(|user| user.id.0),
+                command,
+                pylon_request_id = Empty,
+            );
+
+            cont(deps).instrument(span).await
+        },
+        HandlerSignature::Entry,
+    )
+}
+
+/// Message text that is only logged with `--log-message-text`.
+pub struct Redacted<'a>(pub &'a str);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if LOG_MESSAGE_TEXT.load(Ordering::Relaxed) {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "[{} characters]", self.0.chars().count())
+        }
+    }
+}
@@ -1,38 +1,61 @@
 use std::sync::Arc;
 
 use clap::Parser;
-use notify::{RecursiveMode, Watcher};
+use eyre::{WrapErr, bail};
 use teloxide::{
-    Bot,
-    dispatching::{HandlerExt, UpdateFilterExt, dialogue::InMemStorage},
+    dispatching::{HandlerExt, UpdateFilterExt, UpdateHandler, dialogue::InMemStorage},
     dptree::{case, deps, entry},
+    error_handlers::LoggingErrorHandler,
     prelude::Dispatcher,
-    types::{CallbackQuery, Message, Update},
+    types::{CallbackQuery, InputFile, Message, Update},
+    update_listeners::webhooks,
 };
-use tokio::{select, sync::mpsc::unbounded_channel};
+use tokio::{sync::mpsc::unbounded_channel, task::JoinSet};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, level_filters::LevelFilter, warn};
-use tracing_appender::rolling;
-use tracing_subscriber::{
-    EnvFilter, Layer, fmt::layer, layer::SubscriberExt, util::SubscriberInitExt,
-};
+use tracing::{error, info, warn};
 
 use crate::{
-    cli::Args,
-    config::Config,
+    alerts::{Alerter, Alerts},
+    audit::AuditLog,
+    bots::Bots,
+    cli::{
+        AdminsCommand, Args, AuditCommand, ChatsCommand, CliCommand, PylonCommand, SettingsCommand,
+    },
+    config::{Config, DEFAULT_BOT},
     endpoints::{
-        AdminCommand, Command, State, handle_account_id_input, handle_bot_status_change,
-        handle_callback, is_private_chat, is_public_chat, process_admin_command, process_command,
+        AdminCommand, Command, ForwardedMessages, IssueLists, MessageCache, PrivateCommand, State,
+        cache_message, handle_account_id_input, handle_bot_status_change, handle_callback,
+        handle_chat_default_input, handle_comment_input, handle_issue_description_input,
+        handle_issue_title_input, handle_private_message, handle_reaction, handle_rule_input,
+        handle_rule_match, is_private_chat, is_public_chat, matching_rule, process_admin_command,
+        process_command, process_private_command,
     },
-    pylon::PylonClient,
+    links::LinkChecker,
+    notifications::Notifier,
+    poller::Poller,
+    pylon::PylonWorkspaces,
+    storage::Storage,
+    watcher::SettingsWatcher,
 };
 
-const BOT_USERNAME: &str = "SuccinctPylonBot";
-
+mod alerts;
+mod audit;
+mod bots;
 mod cli;
 mod config;
 mod endpoints;
+mod links;
+mod logging;
+mod manage;
+mod metrics;
+mod monitoring;
+mod notifications;
+mod poller;
 mod pylon;
+mod secret;
+mod storage;
+mod watcher;
+mod webhook;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -41,86 +64,269 @@ async fn main() -> eyre::Result<()> {
     }
 
     let args = Args::parse();
+    let audit_log = AuditLog::new(args.audit_path());
 
-    let (file_layer, _guard) = if let Some(logs_path) = args.logs_path {
-        // Create a rolling file appender
-        let file_appender = rolling::never(logs_path, "logs.txt");
-
-        // Create a layer that writes to the file
-        let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-        let layer = layer()
-            .compact()
-            .with_target(false)
-            .with_writer(non_blocking)
-            .with_filter(LevelFilter::INFO);
-
-        (Some(layer), Some(_guard))
-    } else {
-        (None, None)
-    };
-
-    tracing_subscriber::registry()
-        .with(
-            layer().compact().with_target(false).with_filter(
-                EnvFilter::builder()
-                    .with_default_directive(LevelFilter::INFO.into())
-                    .from_env_lossy(),
-            ),
-        )
-        .with(file_layer)
-        .init();
+    match &args.command {
+        None | Some(CliCommand::Run) => run(args).await,
+        Some(CliCommand::Chats { command }) => {
+            let config = Config::try_new(args.settings_path())?;
+
+            match command {
+                ChatsCommand::List => manage::list_chats(&config).await,
+                ChatsCommand::Link {
+                    chat_id,
+                    account_id,
+                    workspace,
+                } => {
+                    let pylon_client =
+                        PylonWorkspaces::try_new(&config.get().await, args.pylon_api_token()?)
+                            .and_then(|workspaces| workspaces.get(workspace))
+                            .wrap_err_with(|| {
+                                format!(
+                                    "Can't check the account {account_id} in workspace {workspace}"
+                                )
+                            })?;
+
+                    manage::link_chat(
+                        &config,
+                        &audit_log,
+                        &pylon_client,
+                        *chat_id,
+                        workspace,
+                        account_id,
+                    )
+                    .await
+                }
+                ChatsCommand::Unlink { chat_id } => {
+                    manage::unlink_chat(&config, &audit_log, *chat_id).await
+                }
+            }
+        }
+        Some(CliCommand::Admins { command }) => {
+            let config = Config::try_new(args.settings_path())?;
+
+            match command {
+                AdminsCommand::Add { username } => {
+                    manage::set_admin(&config, &audit_log, username, true).await
+                }
+                AdminsCommand::Remove { username } => {
+                    manage::set_admin(&config, &audit_log, username, false).await
+                }
+            }
+        }
+        Some(CliCommand::Settings { command }) => match command {
+            SettingsCommand::Validate => manage::validate_settings(&args.settings_path()),
+            SettingsCommand::Migrate => manage::migrate_settings(&args.settings_path()),
+        },
+        Some(CliCommand::Pylon {
+            command: PylonCommand::CheckAccounts,
+        }) => {
+            let config = Config::try_new(args.settings_path())?;
+            let workspaces = pylon_workspaces(&args, &config).await?;
+
+            manage::check_accounts(&config, &workspaces).await
+        }
+        Some(CliCommand::Audit {
+            command: AuditCommand::Export { format, filter },
+        }) => manage::export_audit(&audit_log, *format, filter.as_deref()).await,
+    }
+}
+
+async fn pylon_workspaces(args: &Args, config: &Config) -> eyre::Result<PylonWorkspaces> {
+    let workspaces = PylonWorkspaces::try_new(&config.get().await, args.pylon_api_token()?)?;
+
+    if workspaces.is_empty() {
+        bail!("--pylon-api-token or pylon_workspaces in the settings is required");
+    }
 
-    let settings_path = args
-        .settings_path
-        .unwrap_or_else(|| "./settings.toml".to_string());
+    Ok(workspaces)
+}
+
+async fn run(args: Args) -> eyre::Result<()> {
+    let _guard = logging::init(&args)?;
+
+    let settings_path = args.settings_path();
     let config = Arc::new(Config::try_new(settings_path.clone())?);
-    let pylon_client = Arc::new(PylonClient::new(args.pylon_api_token.clone()));
-    let config_reload = config.clone();
+    let storage = Arc::new(Storage::try_new(args.storage_path(), args.audit_path())?);
+    let workspaces = Arc::new(pylon_workspaces(&args, &config).await?);
+    let telegram_token = args.telegram_token()?;
+    let (events_tx, events_rx) = unbounded_channel();
+    let (alerts, alerts_rx) = Alerts::new();
     let token = CancellationToken::new();
-    let cloned_token = token.clone();
 
-    // Watch settings file for updates
-    tokio::spawn(async move {
-        let (tx, mut rx) = unbounded_channel();
+    // Reload settings when their file changes
+    tokio::spawn(
+        SettingsWatcher::new(config.clone(), storage.clone(), alerts.clone()).run(token.clone()),
+    );
 
-        let mut watcher = notify::recommended_watcher(move |res| {
-            let _ = tx.send(res);
-        })
-        .unwrap();
+    // Poll Pylon for issue updates, unless Pylon pushes them
+    if args.webhook_address.is_none() {
+        tokio::spawn(
+            Poller::new(
+                workspaces.clone(),
+                config.clone(),
+                storage.clone(),
+                events_tx.clone(),
+            )
+            .run(token.clone()),
+        );
+    } else if config.get().await.poller.enabled {
+        info!("Pylon webhooks are received, the poller is not started");
+    }
 
-        watcher
-            .watch(settings_path.as_ref(), RecursiveMode::NonRecursive)
-            .unwrap();
+    // Receive Pylon webhooks
+    if let (Some(address), Some(secret)) = (args.webhook_address, args.pylon_webhook_secret) {
+        let token = token.clone();
 
-        loop {
-            select! {
-                _ = cloned_token.cancelled() => {
-                     break;
-                }
-                Some(res) = rx.recv() => {
-                    match res {
-                        Ok(event) => {
-                            if event.kind.is_modify()
-                                && config_reload.reload().await.is_ok() {
-                                    info!("Settings reloaded");
-                                }
-                        }
-                        Err(e) => error!("watch error: {:?}", e),
-                    }
-                }
+        tokio::spawn(async move {
+            if let Err(err) =
+                webhook::serve(address, secret.expose().to_string(), events_tx, token).await
+            {
+                error!("Pylon webhook server failed: {err}");
+            }
+        });
+    }
+
+    let bots = Arc::new(Bots::try_new(&config.get().await, telegram_token).await?);
+
+    // Serve health checks and metrics
+    if let Some(address) = args.monitoring_address {
+        let bots = bots.clone();
+        let workspaces = workspaces.clone();
+        let token = token.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = monitoring::serve(address, bots, workspaces, token).await {
+                error!("Monitoring server failed: {err}");
+            }
+        });
+    }
+
+    // Forward errors to admins
+    tokio::spawn(Alerter::new(bots.default_bot(), config.clone()).run(alerts_rx, token.clone()));
+
+    // Report chats and Pylon accounts that can't be reached anymore
+    tokio::spawn(
+        LinkChecker::new(bots.clone(), workspaces.clone(), config.clone()).run(token.clone()),
+    );
+
+    // Notify chats and subscribers of issue updates
+    tokio::spawn(
+        Notifier::new(bots.clone(), config.clone(), storage.clone()).run(events_rx, token.clone()),
+    );
+
+    // Only the default bot can receive updates with a webhook
+    let mut webhook = args
+        .telegram_webhook_url
+        .zip(args.telegram_webhook_address)
+        .map(|(url, address)| {
+            let mut options = webhooks::Options::new(address, url);
+
+            if let Some(secret) = args.telegram_webhook_secret {
+                options = options.secret_token(secret.expose().to_string());
+            }
+
+            if let Some(certificate) = args.telegram_webhook_certificate {
+                options = options.certificate(InputFile::file(certificate));
             }
+
+            options
+        });
+    let mut dispatchers = JoinSet::new();
+
+    for (bot, identity) in bots.iter() {
+        info!("Starting bot {}...", identity.name);
+
+        let alerts = alerts.clone();
+        let mut dispatcher = Dispatcher::builder(bot.clone(), handler())
+            .dependencies(deps![
+                workspaces.clone(),
+                config.clone(),
+                storage.clone(),
+                identity.clone(),
+                MessageCache::new(),
+                ForwardedMessages::new(),
+                IssueLists::new(),
+                InMemStorage::<State>::new()
+            ])
+            .enable_ctrlc_handler()
+            .error_handler(Arc::new(move |err| {
+                metrics::TELEGRAM_ERRORS.inc();
+                error!("{err:#}");
+                alerts.report(&err);
+                Box::pin(async {})
+            }))
+            .build();
+
+        if identity.name == DEFAULT_BOT
+            && let Some(options) = webhook.take()
+        {
+            let address = options.address;
+            let listener = webhooks::axum(bot.clone(), options).await?;
+
+            info!("Receiving Telegram updates on {address}");
+            dispatchers.spawn(async move {
+                dispatcher
+                    .dispatch_with_listener(
+                        listener,
+                        LoggingErrorHandler::with_custom_text("Telegram webhook error"),
+                    )
+                    .await
+            });
+        } else {
+            dispatchers.spawn(async move { dispatcher.dispatch().await });
         }
-    });
+    }
 
-    let bot = Bot::from_env();
+    dispatchers.join_all().await;
+
+    token.cancel();
+
+    Ok(())
+}
 
-    let all_handlers = entry()
+/// Handlers of the updates of a bot.
+fn handler() -> UpdateHandler<eyre::Report> {
+    logging::update_span()
+        .branch(
+            Update::filter_message()
+                .filter(is_public_chat)
+                .inspect_async(cache_message),
+        )
         .branch(
             Update::filter_message()
                 .enter_dialogue::<Message, InMemStorage<State>, State>()
+                .filter(|state: State, message: Message| state.is_answered_by(&message))
+                .branch(
+                    case![State::WaitingForAccountId {
+                        user_id,
+                        chat_id,
+                        workspace
+                    }]
+                    .endpoint(handle_account_id_input),
+                )
+                .branch(
+                    case![State::WaitingForIssueTitle { user_id }]
+                        .endpoint(handle_issue_title_input),
+                )
+                .branch(
+                    case![State::WaitingForIssueDescription { user_id, title }]
+                        .endpoint(handle_issue_description_input),
+                )
+                .branch(
+                    case![State::WaitingForChatDefault {
+                        user_id,
+                        chat_id,
+                        field
+                    }]
+                    .endpoint(handle_chat_default_input),
+                )
                 .branch(
-                    case![State::WaitingForAccountId { chat_id }].endpoint(handle_account_id_input),
+                    case![State::WaitingForRule { user_id, chat_id }].endpoint(handle_rule_input),
+                )
+                .branch(
+                    case![State::WaitingForComment { user_id, number }]
+                        .endpoint(handle_comment_input),
                 ),
         )
         .branch(
@@ -130,6 +336,7 @@ async fn main() -> eyre::Result<()> {
                     entry()
                         .filter(is_public_chat)
                         .filter_command::<Command>()
+                        .enter_dialogue::<Message, InMemStorage<State>, State>()
                         .endpoint(process_command),
                 )
                 .branch(
@@ -138,27 +345,29 @@ async fn main() -> eyre::Result<()> {
                         .filter_command::<AdminCommand>()
                         .endpoint(process_admin_command),
                 )
+                .branch(
+                    entry()
+                        .filter(is_private_chat)
+                        .filter_command::<PrivateCommand>()
+                        .endpoint(process_private_command),
+                )
+                .branch(
+                    entry()
+                        .filter(is_private_chat)
+                        .endpoint(handle_private_message),
+                )
+                .branch(
+                    entry()
+                        .filter(is_public_chat)
+                        .filter_map_async(matching_rule)
+                        .endpoint(handle_rule_match),
+                )
                 .branch(Update::filter_message().endpoint(handle_bot_status_change)),
         )
+        .branch(Update::filter_message_reaction_updated().endpoint(handle_reaction))
         .branch(
             Update::filter_callback_query()
                 .enter_dialogue::<CallbackQuery, InMemStorage<State>, State>()
                 .endpoint(handle_callback),
-        );
-
-    info!("Starting bot...");
-    Dispatcher::builder(bot, all_handlers)
-        .dependencies(deps![pylon_client, config, InMemStorage::<State>::new()])
-        .enable_ctrlc_handler()
-        .error_handler(Arc::new(|err| {
-            error!("{err}");
-            Box::pin(async {})
-        }))
-        .build()
-        .dispatch()
-        .await;
-
-    token.cancel();
-
-    Ok(())
+        )
 }
@@ -0,0 +1,205 @@
+use std::{fs, io::stdout};
+
+use eyre::{OptionExt, bail};
+
+use crate::{
+    audit::{self, AuditAction, AuditEntry, AuditLog},
+    cli::ExportFormat,
+    config::{Config, Settings},
+    pylon::{PylonClient, PylonWorkspaces},
+};
+
+/// Prints the chats and the Pylon accounts they are linked to.
+pub async fn list_chats(config: &Config) -> eyre::Result<()> {
+    let settings = config.get().await;
+    let mut chats = settings
+        .tg_chats_to_pylon_accounts
+        .iter()
+        .collect::<Vec<_>>();
+    chats.sort();
+
+    for (chat_id, account_id) in chats {
+        if account_id.is_empty() {
+            println!("{chat_id}\t(unlinked)");
+        } else {
+            println!("{chat_id}\t{}\t{account_id}", settings.workspace(chat_id));
+        }
+    }
+
+    Ok(())
+}
+
+/// Links a chat to a Pylon account, checking that the account exists in the workspace of
+/// `pylon_client`.
+pub async fn link_chat(
+    config: &Config,
+    audit_log: &AuditLog,
+    pylon_client: &PylonClient,
+    chat_id: i64,
+    workspace: &str,
+    account_id: &str,
+) -> eyre::Result<()> {
+    let account = pylon_client
+        .get_account(account_id)
+        .await?
+        .ok_or_eyre("Account not found in Pylon")?;
+
+    println!(
+        "Linking chat {chat_id} to Pylon account '{}'",
+        account.name.unwrap_or_default()
+    );
+
+    let mut settings = config.get().await;
+    settings.link(&chat_id.to_string(), workspace, account_id);
+    config.save(settings)?;
+
+    audit_log
+        .record(
+            AuditEntry::new(None, AuditAction::Link, chat_id.to_string())
+                .details(format!("account {account_id} in workspace {workspace}")),
+        )
+        .await
+}
+
+/// Removes the Pylon account of a chat. The chat is kept, and listed as unlinked.
+pub async fn unlink_chat(config: &Config, audit_log: &AuditLog, chat_id: i64) -> eyre::Result<()> {
+    let mut settings = config.get().await;
+    let Some(account_id) = settings
+        .tg_chats_to_pylon_accounts
+        .get_mut(&chat_id.to_string())
+        .filter(|account_id| !account_id.is_empty())
+    else {
+        bail!("Chat {chat_id} is not linked to a Pylon account");
+    };
+    let previous = std::mem::take(account_id);
+    config.save(settings)?;
+
+    audit_log
+        .record(
+            AuditEntry::new(None, AuditAction::Unlink, chat_id.to_string())
+                .details(format!("account {previous}")),
+        )
+        .await
+}
+
+/// Adds or removes a bot admin by Telegram username.
+pub async fn set_admin(
+    config: &Config,
+    audit_log: &AuditLog,
+    username: &str,
+    admin: bool,
+) -> eyre::Result<()> {
+    let username = username.trim_start_matches('@');
+    let mut settings = config.get().await;
+
+    let (changed, action) = if admin {
+        (
+            settings.bot_admins.insert(username.to_string()),
+            AuditAction::AdminAdd,
+        )
+    } else {
+        (
+            settings.bot_admins.remove(username),
+            AuditAction::AdminRemove,
+        )
+    };
+
+    if !changed {
+        println!("Nothing to do for {username}");
+        return Ok(());
+    }
+
+    config.save(settings)?;
+
+    audit_log
+        .record(AuditEntry::new(None, action, username))
+        .await
+}
+
+/// Loads and validates the settings file, and prints a summary.
+pub fn validate_settings(settings_path: &str) -> eyre::Result<()> {
+    let settings = Settings::load(settings_path)?;
+    let linked = settings
+        .tg_chats_to_pylon_accounts
+        .values()
+        .filter(|account_id| !account_id.is_empty())
+        .count();
+
+    println!(
+        "{settings_path} is valid: {} chats ({linked} linked), {} admins",
+        settings.tg_chats_to_pylon_accounts.len(),
+        settings.bot_admins.len()
+    );
+
+    Ok(())
+}
+
+/// Rewrites the settings file in the current format, after copying it to `<path>.bak`.
+///
+/// Missing sections are written with their default values.
+pub fn migrate_settings(settings_path: &str) -> eyre::Result<()> {
+    let settings = Settings::load(settings_path)?;
+    let backup_path = format!("{settings_path}.bak");
+
+    fs::copy(settings_path, &backup_path)?;
+    confy::store_path(settings_path, settings)?;
+
+    println!("{settings_path} migrated, the previous version is in {backup_path}");
+
+    Ok(())
+}
+
+/// Checks that the Pylon accounts linked to chats exist, failing if any is missing.
+pub async fn check_accounts(config: &Config, workspaces: &PylonWorkspaces) -> eyre::Result<()> {
+    let settings = config.get().await;
+    let mut chats = settings
+        .tg_chats_to_pylon_accounts
+        .iter()
+        .collect::<Vec<_>>();
+    chats.sort();
+    let mut missing = 0;
+
+    for (chat_id, account_id) in chats {
+        if account_id.is_empty() {
+            continue;
+        }
+
+        let workspace = settings.workspace(chat_id);
+
+        match workspaces.get(&workspace)?.get_account(account_id).await? {
+            Some(account) => println!(
+                "✅ {chat_id}\t{workspace}\t{account_id}\t{}",
+                account.name.unwrap_or_default()
+            ),
+            None => {
+                println!("❌ {chat_id}\t{workspace}\t{account_id}\tnot found");
+                missing += 1;
+            }
+        }
+    }
+
+    if missing > 0 {
+        bail!("{missing} linked accounts were not found in Pylon");
+    }
+
+    Ok(())
+}
+
+/// Writes the entries of the audit log containing `filter` to stdout.
+pub async fn export_audit(
+    audit_log: &AuditLog,
+    format: ExportFormat,
+    filter: Option<&str>,
+) -> eyre::Result<()> {
+    let entries = audit_log
+        .entries()
+        .await?
+        .into_iter()
+        .filter(|entry| filter.is_none_or(|filter| entry.matches(filter)))
+        .collect::<Vec<_>>();
+
+    match format {
+        ExportFormat::Jsonl => audit::write_jsonl(&entries, stdout().lock()),
+        ExportFormat::Csv => audit::write_csv(&entries, stdout().lock()),
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+    core::Collector,
+};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+pub static COMMANDS_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("commands_received_total", "Bot commands received"),
+            &["command"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static ISSUES_CREATED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("issues_created_total", "Pylon issues created by the bot").unwrap())
+});
+
+pub static PYLON_REQUESTS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "pylon_request_duration_seconds",
+                "Duration of the requests to the Pylon API",
+            ),
+            &["endpoint", "status"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static TELEGRAM_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(
+        IntCounter::new(
+            "telegram_errors_total",
+            "Errors while handling Telegram updates",
+        )
+        .unwrap(),
+    )
+});
+
+pub static SETTINGS_RELOADS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("settings_reloads_total", "Reloads of the settings file"),
+            &["result"],
+        )
+        .unwrap(),
+    )
+});
+
+fn register<T: Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY.register(Box::new(collector.clone())).unwrap();
+    collector
+}
+
+/// Returns all metrics in the Prometheus text format.
+pub fn encode() -> eyre::Result<String> {
+    // Register the metrics that were not used yet
+    LazyLock::force(&COMMANDS_RECEIVED);
+    LazyLock::force(&ISSUES_CREATED);
+    LazyLock::force(&PYLON_REQUESTS);
+    LazyLock::force(&TELEGRAM_ERRORS);
+    LazyLock::force(&SETTINGS_RELOADS);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+
+    Ok(String::from_utf8(buffer)?)
+}
@@ -0,0 +1,77 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{Router, extract::State, http::StatusCode, routing::get};
+use teloxide::prelude::Requester;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{bots::Bots, metrics, pylon::PylonWorkspaces};
+
+struct MonitoringState {
+    bots: Arc<Bots>,
+    workspaces: Arc<PylonWorkspaces>,
+}
+
+/// Serves the health, readiness and metrics endpoints on `address` until `token` is cancelled.
+pub async fn serve(
+    address: SocketAddr,
+    bots: Arc<Bots>,
+    workspaces: Arc<PylonWorkspaces>,
+    token: CancellationToken,
+) -> eyre::Result<()> {
+    let state = Arc::new(MonitoringState { bots, workspaces });
+    let router = Router::new()
+        .route("/healthz", get(health))
+        .route("/readyz", get(readiness))
+        .route("/metrics", get(export_metrics))
+        .with_state(state);
+    let listener = TcpListener::bind(address).await?;
+
+    info!("Serving health checks and metrics on {address}");
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(token.cancelled_owned())
+        .await?;
+
+    Ok(())
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+/// Checks that all the Telegram bots and Pylon workspaces can be reached.
+async fn readiness(State(state): State<Arc<MonitoringState>>) -> (StatusCode, String) {
+    for (bot, identity) in state.bots.iter() {
+        if let Err(err) = bot.get_me().await {
+            warn!(
+                "Readiness check failed, Telegram bot {}: {err}",
+                identity.name
+            );
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Telegram bot {}: {err}", identity.name),
+            );
+        }
+    }
+
+    for (name, pylon_client) in state.workspaces.iter() {
+        if let Err(err) = pylon_client.get_me().await {
+            warn!("Readiness check failed, Pylon workspace {name}: {err}");
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("Pylon workspace {name}: {err}"),
+            );
+        }
+    }
+
+    (StatusCode::OK, "ok".to_string())
+}
+
+async fn export_metrics() -> (StatusCode, String) {
+    match metrics::encode() {
+        Ok(metrics) => (StatusCode::OK, metrics),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
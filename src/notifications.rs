@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude::Requester,
+    types::{ChatId, MessageId, ParseMode, ThreadId, UserId},
+};
+use tokio::{select, sync::mpsc::UnboundedReceiver};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{bots::Bots, config::Config, storage::Storage};
+
+/// Change of an issue created by the bot, reported by Pylon webhooks or by polling Pylon.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueEvent {
+    StateChanged { issue_id: String, state: String },
+}
+
+/// Notifies the chats issues were created from, and their subscribers, of the issue events.
+pub struct Notifier {
+    bots: Arc<Bots>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+}
+
+impl Notifier {
+    pub fn new(bots: Arc<Bots>, config: Arc<Config>, storage: Arc<Storage>) -> Self {
+        Self {
+            bots,
+            config,
+            storage,
+        }
+    }
+
+    pub async fn run(self, mut events: UnboundedReceiver<IssueEvent>, token: CancellationToken) {
+        loop {
+            select! {
+                _ = token.cancelled() => {
+                    break;
+                }
+                Some(event) = events.recv() => {
+                    if let Err(err) = self.notify(&event).await {
+                        error!("Failed to notify {event:?}: {err}");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn notify(&self, event: &IssueEvent) -> eyre::Result<()> {
+        match event {
+            IssueEvent::StateChanged { issue_id, state } => {
+                // Issues not created by the bot are not tracked
+                let Some(previous_state) = self.storage.set_issue_state(issue_id, state).await?
+                else {
+                    return Ok(());
+                };
+
+                // Nothing changed, or the state was not known yet
+                if previous_state.is_none() || previous_state.as_ref() == Some(state) {
+                    return Ok(());
+                }
+
+                let Some(issue) = self.storage.issue(issue_id).await else {
+                    return Ok(());
+                };
+
+                info!("Issue #{} is now '{state}'", issue.number);
+
+                let text = format!(
+                    "🔄 Issue [\\#{}]({}) is now *{}*",
+                    issue.number,
+                    issue.link,
+                    state.replace('_', " ")
+                );
+                let settings = self.config.get().await;
+                // Subscribers started the bot of the chat the issue was created from
+                let bot = self.bots.for_chat(&settings, &issue.chat_id.to_string())?;
+
+                if settings
+                    .chat_settings(&issue.chat_id.to_string())
+                    .notify_state_changes
+                {
+                    let mut request = bot
+                        .send_message(ChatId(issue.chat_id), &text)
+                        .parse_mode(ParseMode::MarkdownV2);
+
+                    // Announce the change in the forum topic the issue comes from
+                    if let Some(topic_id) = issue.topic_id {
+                        request = request.message_thread_id(ThreadId(MessageId(topic_id)));
+                    }
+
+                    request.await?;
+                }
+
+                for user_id in &issue.subscribers {
+                    if let Err(err) = bot
+                        .send_message(UserId(*user_id), &text)
+                        .parse_mode(ParseMode::MarkdownV2)
+                        .await
+                    {
+                        warn!("Failed to notify subscriber {user_id}: {err}");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{TimeDelta, Utc};
+use tokio::{select, sync::mpsc::UnboundedSender, time::sleep};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{
+    config::{Config, Settings},
+    notifications::IssueEvent,
+    pylon::PylonWorkspaces,
+    storage::Storage,
+};
+
+/// Polls Pylon for the state of the issues created by the bot, and sends an event for each
+/// state change, like Pylon webhooks would.
+pub struct Poller {
+    workspaces: Arc<PylonWorkspaces>,
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    events: UnboundedSender<IssueEvent>,
+    /// Index of the next issue to check, when they don't all fit in a batch.
+    next_issue: usize,
+}
+
+impl Poller {
+    pub fn new(
+        workspaces: Arc<PylonWorkspaces>,
+        config: Arc<Config>,
+        storage: Arc<Storage>,
+        events: UnboundedSender<IssueEvent>,
+    ) -> Self {
+        Self {
+            workspaces,
+            config,
+            storage,
+            events,
+            next_issue: 0,
+        }
+    }
+
+    pub async fn run(mut self, token: CancellationToken) {
+        loop {
+            // Settings are read on each poll to pick up reloads
+            let settings = self.config.get().await;
+
+            select! {
+                _ = token.cancelled() => {
+                    break;
+                }
+                _ = sleep(Duration::from_secs(settings.poller.interval_secs.max(1))) => {
+                    self.poll(&settings).await;
+                }
+            }
+        }
+    }
+
+    async fn poll(&mut self, settings: &Settings) {
+        if !settings.poller.enabled {
+            return;
+        }
+
+        if let Some(quiet_hours) = &settings.poller.quiet_hours
+            && quiet_hours.contains(Utc::now().time())
+        {
+            debug!("Not polling Pylon during quiet hours");
+            return;
+        }
+
+        let oldest = Utc::now() - TimeDelta::days(settings.poller.max_age_days);
+        let mut issues = self
+            .storage
+            .issues()
+            .await
+            .into_iter()
+            .filter(|issue| issue.created_at >= oldest && issue.state.as_deref() != Some("closed"))
+            .collect::<Vec<_>>();
+        issues.sort_by_key(|issue| issue.number);
+
+        if self.next_issue >= issues.len() {
+            self.next_issue = 0;
+        }
+
+        let batch = issues
+            .iter()
+            .skip(self.next_issue)
+            .take(settings.poller.batch_size.max(1));
+        let mut checked = 0;
+
+        for issue in batch {
+            checked += 1;
+
+            let pylon_client = match self
+                .workspaces
+                .for_chat(settings, &issue.chat_id.to_string())
+            {
+                Ok(pylon_client) => pylon_client,
+                Err(err) => {
+                    warn!("Failed to poll issue #{}: {err}", issue.number);
+                    continue;
+                }
+            };
+
+            match pylon_client.get_issue(&issue.id).await {
+                Ok(Some(current)) => {
+                    if let Some(state) = current.state
+                        && issue.state.as_ref() != Some(&state)
+                    {
+                        let _ = self.events.send(IssueEvent::StateChanged {
+                            issue_id: issue.id.clone(),
+                            state,
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Failed to poll issue #{}: {err}", issue.number),
+            }
+        }
+
+        self.next_issue += checked;
+
+        debug!("Polled {checked} of {} issues", issues.len());
+    }
+}
@@ -1,8 +1,40 @@
 use serde::{Deserialize, Serialize};
 
+/// Issue priorities supported by Pylon.
+pub const PRIORITIES: [&str; 4] = ["urgent", "high", "medium", "low"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Issue {
+    pub account_id: String,
+    pub title: String,
+    pub body_html: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_fields: Vec<CustomFieldValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachment_urls: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomFieldValue {
+    pub slug: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IssueUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Issue<'a> {
-    pub account_id: &'a str,
-    pub title: &'a str,
-    pub body_html: &'a str,
+pub struct Note {
+    pub body_html: String,
 }
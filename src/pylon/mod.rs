@@ -1,79 +1,345 @@
 mod issue;
-use eyre::eyre;
-pub use issue::Issue;
+use std::{fmt, time::Instant};
+
+pub use issue::{CustomFieldValue, Issue, IssueUpdate, Note, PRIORITIES};
+use reqwest::{
+    RequestBuilder, Response,
+    multipart::{Form, Part},
+};
+use serde::de::DeserializeOwned;
 
 mod responses;
-pub use responses::SuccessResponse;
+pub mod webhook;
+mod workspaces;
+pub use responses::{IssueResponse, SuccessResponse};
+use serde_json::json;
+use tracing::{Span, debug};
+pub use workspaces::PylonWorkspaces;
 
-use crate::pylon::responses::{CreateIssueResponse, ErrorResponse, GetAccountResponse};
+use crate::{
+    metrics,
+    pylon::responses::{
+        CreateAttachmentResponse, CreateIssueResponse, ErrorResponse, GetAccountResponse, Tag,
+        Team, User,
+    },
+    secret::Secret,
+};
 
 const PYLON_API_URL: &str = "https://api.usepylon.com";
 
+/// Issue states that are not closed.
+pub const OPEN_STATES: [&str; 4] = ["new", "waiting_on_you", "waiting_on_customer", "on_hold"];
+
+/// Filter of [`PylonClient::list_issues`].
+#[derive(Debug, Default, Clone)]
+pub struct IssueFilter {
+    pub account_id: Option<String>,
+    pub states: Vec<String>,
+}
+
 pub struct PylonClient {
-    api_token: String,
+    api_token: Secret,
+    base_url: String,
     http_client: reqwest::Client,
 }
 
 impl PylonClient {
-    pub fn new(api_token: String) -> Self {
+    pub fn new(api_token: impl Into<Secret>) -> Self {
+        Self::with_base_url(api_token, PYLON_API_URL.to_string())
+    }
+
+    /// Creates a client for a Pylon API served at `base_url`, without trailing slash.
+    pub fn with_base_url(api_token: impl Into<Secret>, base_url: String) -> Self {
         let http_client = reqwest::Client::new();
 
         PylonClient {
-            api_token,
+            api_token: api_token.into(),
+            base_url,
             http_client,
         }
     }
 
-    pub async fn create_issue(
+    pub async fn create_issue(&self, issue: &Issue) -> Result<CreateIssueResponse, eyre::Error> {
+        let response = self
+            .send(
+                "create_issue",
+                self.http_client
+                    .post(format!("{}/issues", self.base_url))
+                    .json(issue),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    pub async fn create_attachment(
         &self,
-        title: &str,
-        body_html: &str,
-        account_id: &str,
-    ) -> Result<CreateIssueResponse, eyre::Error> {
-        let issue = Issue {
-            account_id,
-            title,
-            body_html,
-        };
+        file_name: &str,
+        content: Vec<u8>,
+    ) -> Result<CreateAttachmentResponse, eyre::Error> {
+        let form = Form::new().part(
+            "file",
+            Part::bytes(content).file_name(file_name.to_string()),
+        );
 
         let response = self
-            .http_client
-            .post(format!("{PYLON_API_URL}/issues"))
-            .bearer_auth(&self.api_token)
-            .json(&issue)
-            .send()
+            .send(
+                "create_attachment",
+                self.http_client
+                    .post(format!("{}/attachments", self.base_url))
+                    .multipart(form),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    /// Gets an issue by id or number.
+    pub async fn get_issue(&self, id: &str) -> Result<Option<IssueResponse>, eyre::Error> {
+        let response = self
+            .send(
+                "get_issue",
+                self.http_client
+                    .get(format!("{}/issues/{id}", self.base_url)),
+            )
+            .await?;
+
+        if response.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        parse_response(response).await.map(Some)
+    }
+
+    pub async fn update_issue(
+        &self,
+        id: &str,
+        update: &IssueUpdate,
+    ) -> Result<IssueResponse, eyre::Error> {
+        let response = self
+            .send(
+                "update_issue",
+                self.http_client
+                    .patch(format!("{}/issues/{id}", self.base_url))
+                    .json(update),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    /// Adds an internal note to an issue.
+    pub async fn create_note(&self, id: &str, note: &Note) -> Result<(), eyre::Error> {
+        let response = self
+            .send(
+                "create_note",
+                self.http_client
+                    .post(format!("{}/issues/{id}/note", self.base_url))
+                    .json(note),
+            )
+            .await?;
+
+        parse_response::<serde_json::Value>(response).await?;
+
+        Ok(())
+    }
+
+    /// Lists the issues matching `filter`, at most `limit` per page.
+    ///
+    /// Returns the issues and the cursor of the next page, if any.
+    pub async fn list_issues(
+        &self,
+        filter: &IssueFilter,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<(Vec<IssueResponse>, Option<String>), eyre::Error> {
+        let mut subfilters = Vec::new();
+
+        if let Some(account_id) = &filter.account_id {
+            subfilters.push(json!({
+                "field": "account_id",
+                "operator": "equals",
+                "value": account_id,
+            }));
+        }
+
+        if !filter.states.is_empty() {
+            subfilters.push(json!({
+                "field": "state",
+                "operator": "in",
+                "values": filter.states,
+            }));
+        }
+
+        let mut body = json!({
+            "filter": {
+                "operator": "and",
+                "subfilters": subfilters,
+            },
+            "limit": limit,
+        });
+
+        if let Some(cursor) = cursor {
+            body["cursor"] = json!(cursor);
+        }
+
+        let response = self
+            .send(
+                "list_issues",
+                self.http_client
+                    .post(format!("{}/issues/search", self.base_url))
+                    .json(&body),
+            )
             .await?;
 
         match response.status().as_u16() {
             200 => {
-                let response = response.json::<SuccessResponse<_>>().await?;
-                Ok(response.data)
+                let response = response
+                    .json::<SuccessResponse<Vec<IssueResponse>>>()
+                    .await?;
+                record_request_id(response.request_id.as_deref());
+                let cursor = response
+                    .pagination
+                    .filter(|pagination| pagination.has_next_page)
+                    .and_then(|pagination| pagination.cursor);
+
+                Ok((response.data, cursor))
             }
             _ => {
                 let response = response.json::<ErrorResponse>().await?;
-                Err(eyre!("{}", response.errors.join(", ")))
+                record_request_id(response.request_id.as_deref());
+                Err(PylonError::from(response).into())
             }
         }
     }
 
     pub async fn get_account(&self, id: &str) -> Result<Option<GetAccountResponse>, eyre::Error> {
         let response = self
-            .http_client
-            .get(format!("{PYLON_API_URL}/accounts/{id}"))
-            .bearer_auth(&self.api_token)
-            .send()
+            .send(
+                "get_account",
+                self.http_client
+                    .get(format!("{}/accounts/{id}", self.base_url)),
+            )
             .await?;
 
-        match response.status().as_u16() {
-            200 => {
-                let response = response.json::<SuccessResponse<_>>().await?;
-                Ok(Some(response.data))
-            }
-            404 => Ok(None),
-            _ => {
-                let response = response.json::<ErrorResponse>().await?;
-                Err(eyre!("{}", response.errors.join(", ")))
-            }
+        if response.status().as_u16() == 404 {
+            return Ok(None);
         }
+
+        parse_response(response).await.map(Some)
+    }
+
+    /// Checks that the Pylon API can be reached with the API token.
+    pub async fn get_me(&self) -> Result<(), eyre::Error> {
+        let response = self
+            .send(
+                "get_me",
+                self.http_client.get(format!("{}/me", self.base_url)),
+            )
+            .await?;
+
+        parse_response::<serde_json::Value>(response).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_tags(&self) -> Result<Vec<Tag>, eyre::Error> {
+        let response = self
+            .send(
+                "get_tags",
+                self.http_client.get(format!("{}/tags", self.base_url)),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    pub async fn get_teams(&self) -> Result<Vec<Team>, eyre::Error> {
+        let response = self
+            .send(
+                "get_teams",
+                self.http_client.get(format!("{}/teams", self.base_url)),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    pub async fn get_users(&self) -> Result<Vec<User>, eyre::Error> {
+        let response = self
+            .send(
+                "get_users",
+                self.http_client.get(format!("{}/users", self.base_url)),
+            )
+            .await?;
+
+        parse_response(response).await
+    }
+
+    /// Sends an authenticated request, recording its duration and status.
+    async fn send(
+        &self,
+        endpoint: &str,
+        request: RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        let start = Instant::now();
+        let response = request.bearer_auth(self.api_token.expose()).send().await;
+        let status = match &response {
+            Ok(response) => response.status().as_str().to_string(),
+            Err(_) => "error".to_string(),
+        };
+
+        metrics::PYLON_REQUESTS
+            .with_label_values(&[endpoint, &status])
+            .observe(start.elapsed().as_secs_f64());
+
+        response
+    }
+}
+
+/// Error returned by the Pylon API.
+#[derive(Debug)]
+pub struct PylonError {
+    pub errors: Vec<String>,
+    pub request_id: Option<String>,
+}
+
+impl From<ErrorResponse> for PylonError {
+    fn from(response: ErrorResponse) -> Self {
+        Self {
+            errors: response.errors,
+            request_id: response.request_id,
+        }
+    }
+}
+
+impl fmt::Display for PylonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.errors.join(", "))
+    }
+}
+
+impl std::error::Error for PylonError {}
+
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T, eyre::Error> {
+    match response.status().as_u16() {
+        200 => {
+            let response = response.json::<SuccessResponse<_>>().await?;
+            record_request_id(response.request_id.as_deref());
+            Ok(response.data)
+        }
+        _ => {
+            let response = response.json::<ErrorResponse>().await?;
+            record_request_id(response.request_id.as_deref());
+            Err(PylonError::from(response).into())
+        }
+    }
+}
+
+/// Records the id of a Pylon request on the current span, to trace it end-to-end.
+fn record_request_id(request_id: Option<&str>) {
+    if let Some(request_id) = request_id {
+        Span::current().record("pylon_request_id", request_id);
+        debug!(pylon_request_id = request_id, "Pylon response");
     }
 }
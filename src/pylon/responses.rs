@@ -3,14 +3,61 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SuccessResponse<T> {
     pub data: T,
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
     pub request_id: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Pagination {
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub has_next_page: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateIssueResponse {
     pub id: Option<String>,
     pub number: Option<u64>,
     pub link: Option<String>,
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateAttachmentResponse {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueResponse {
+    pub id: String,
+    pub number: Option<u64>,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub state: Option<String>,
+    pub account: Option<ObjectRef>,
+    pub assignee: Option<Assignee>,
+    pub created_at: Option<String>,
+    pub latest_message_time: Option<String>,
+}
+
+impl IssueResponse {
+    pub fn account_id(&self) -> Option<&str> {
+        self.account.as_ref().map(|account| account.id.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectRef {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignee {
+    pub id: String,
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +66,40 @@ pub struct GetAccountResponse {
     pub name: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: Option<String>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Team {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl User {
+    /// Whether `handle` (as in `@handle`) designates this user, either by the local part of their
+    /// email or by their name without spaces.
+    pub fn matches_handle(&self, handle: &str) -> bool {
+        let email_handle = self
+            .email
+            .as_deref()
+            .and_then(|email| email.split('@').next());
+        let name_handle = self.name.as_ref().map(|name| name.replace(' ', ""));
+
+        email_handle.is_some_and(|h| h.eq_ignore_ascii_case(handle))
+            || name_handle.is_some_and(|h| h.eq_ignore_ascii_case(handle))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub errors: Vec<String>,
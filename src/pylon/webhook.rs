@@ -0,0 +1,131 @@
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+// The headers, signing scheme and events below are the format the bot expects. They have not been
+// checked against Pylon's webhook documentation nor a captured delivery, so whatever sends the
+// webhooks must be set up to produce them.
+
+/// Header carrying the id of a webhook delivery, only used in logs since it is not signed.
+pub const DELIVERY_ID_HEADER: &str = "x-pylon-delivery-id";
+/// Header carrying the Unix time, in seconds, a webhook delivery was signed at.
+pub const TIMESTAMP_HEADER: &str = "x-pylon-timestamp";
+/// Header carrying the hex encoded HMAC-SHA256 of `{timestamp}.{body}`.
+pub const SIGNATURE_HEADER: &str = "x-pylon-signature";
+
+/// Maximum age of a delivery before it is considered a replay.
+pub const DEFAULT_TOLERANCE: TimeDelta = TimeDelta::minutes(5);
+
+/// Event sent by Pylon webhooks.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event")]
+pub enum WebhookEvent {
+    #[serde(rename = "issue.state_changed")]
+    IssueStateChanged { issue_id: String, state: String },
+    /// Events the bot does not handle.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Webhook request, as received.
+#[derive(Debug, Clone, Copy)]
+pub struct WebhookDelivery<'a> {
+    pub id: &'a str,
+    pub timestamp: &'a str,
+    pub signature: &'a str,
+    pub body: &'a [u8],
+}
+
+#[derive(Debug)]
+pub enum WebhookError {
+    InvalidTimestamp,
+    InvalidSignature,
+    /// The delivery was signed too long ago, or in the future.
+    Expired,
+    /// The delivery was already received, possibly under another id.
+    Duplicate,
+    InvalidPayload(serde_json::Error),
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookError::InvalidTimestamp => write!(f, "invalid timestamp"),
+            WebhookError::InvalidSignature => write!(f, "invalid signature"),
+            WebhookError::Expired => write!(f, "expired delivery"),
+            WebhookError::Duplicate => write!(f, "duplicate delivery"),
+            WebhookError::InvalidPayload(err) => write!(f, "invalid payload: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for WebhookError {}
+
+/// Verifies the deliveries of Pylon webhooks and rejects replays.
+pub struct WebhookVerifier {
+    secret: Vec<u8>,
+    tolerance: TimeDelta,
+    /// Signatures of the deliveries received within the tolerance, with their timestamp. The
+    /// signature identifies a delivery, unlike its id which could be changed to replay it.
+    deliveries: Mutex<HashMap<Vec<u8>, DateTime<Utc>>>,
+}
+
+impl WebhookVerifier {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+            tolerance: DEFAULT_TOLERANCE,
+            deliveries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks the signature, age and uniqueness of a delivery and parses its event.
+    pub fn verify(
+        &self,
+        delivery: &WebhookDelivery,
+        now: DateTime<Utc>,
+    ) -> Result<WebhookEvent, WebhookError> {
+        let timestamp = delivery
+            .timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or(WebhookError::InvalidTimestamp)?;
+
+        let signature =
+            hex::decode(delivery.signature.trim()).map_err(|_| WebhookError::InvalidSignature)?;
+
+        self.mac(delivery.timestamp, delivery.body)
+            .verify_slice(&signature)
+            .map_err(|_| WebhookError::InvalidSignature)?;
+
+        if (now - timestamp).abs() > self.tolerance {
+            return Err(WebhookError::Expired);
+        }
+
+        {
+            let mut deliveries = self.deliveries.lock().unwrap();
+
+            // Older deliveries are rejected as expired anyway
+            deliveries.retain(|_, received| (now - *received).abs() <= self.tolerance);
+
+            if deliveries.insert(signature, timestamp).is_some() {
+                return Err(WebhookError::Duplicate);
+            }
+        }
+
+        serde_json::from_slice(delivery.body).map_err(WebhookError::InvalidPayload)
+    }
+
+    fn mac(&self, timestamp: &str, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac
+    }
+}
@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use eyre::{OptionExt, WrapErr, bail};
+
+use crate::{
+    config::{DEFAULT_WORKSPACE, Settings},
+    pylon::PylonClient,
+    secret::Secret,
+};
+
+/// Pylon clients of the configured workspaces, by name.
+///
+/// Workspaces are read at startup, adding one to the settings requires a restart.
+pub struct PylonWorkspaces {
+    clients: BTreeMap<String, Arc<PylonClient>>,
+}
+
+impl PylonWorkspaces {
+    /// Creates the clients of the workspaces in `settings`, and of the default workspace if
+    /// `default_token` is set.
+    pub fn try_new(settings: &Settings, default_token: Option<Secret>) -> eyre::Result<Self> {
+        let mut clients = BTreeMap::new();
+
+        if let Some(token) = default_token {
+            clients.insert(
+                DEFAULT_WORKSPACE.to_string(),
+                Arc::new(PylonClient::new(token)),
+            );
+        }
+
+        for (name, workspace) in &settings.pylon_workspaces {
+            if clients.contains_key(name) {
+                bail!("Workspace '{name}' is also set with --pylon-api-token");
+            }
+
+            let token = workspace
+                .token()
+                .wrap_err_with(|| format!("Invalid Pylon workspace '{name}'"))?;
+            let client = match &workspace.base_url {
+                Some(base_url) => {
+                    PylonClient::with_base_url(token, base_url.trim_end_matches('/').to_string())
+                }
+                None => PylonClient::new(token),
+            };
+
+            clients.insert(name.clone(), Arc::new(client));
+        }
+
+        Ok(Self { clients })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> eyre::Result<Arc<PylonClient>> {
+        self.clients
+            .get(name)
+            .cloned()
+            .ok_or_eyre(format!("Unknown Pylon workspace '{name}'"))
+    }
+
+    /// Returns the client of the workspace of `chat_id`.
+    pub fn for_chat(&self, settings: &Settings, chat_id: &str) -> eyre::Result<Arc<PylonClient>> {
+        self.get(&settings.workspace(chat_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<PylonClient>)> {
+        self.clients
+            .iter()
+            .map(|(name, client)| (name.as_str(), client))
+    }
+}
@@ -0,0 +1,61 @@
+use std::{convert::Infallible, fmt, fs, path::Path, str::FromStr, sync::LazyLock};
+
+use eyre::WrapErr;
+use regex::Regex;
+
+/// Bearer tokens, such as in `Authorization` headers.
+static BEARER_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/=-]+").unwrap());
+
+/// Telegram bot tokens, such as in Bot API URLs.
+static TELEGRAM_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d{5,}:[A-Za-z0-9_-]{30,}").unwrap());
+
+/// Token or password that is redacted when formatted with `Debug`.
+///
+/// The value is only available with [`Secret::expose`], so that it doesn't end up in logs or
+/// error messages by accident.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Reads a secret from a file, such as a Docker or Kubernetes secret, ignoring the whitespace
+    /// around it.
+    pub fn read_file(path: &Path) -> eyre::Result<Self> {
+        let secret = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read the secret from {}", path.display()))?;
+
+        Ok(Self(secret.trim().to_string()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[redacted]")
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(secret: &str) -> Result<Self, Self::Err> {
+        Ok(Self(secret.to_string()))
+    }
+}
+
+/// Replaces the bearer tokens and Telegram bot tokens in `text`.
+pub fn scrub(text: &str) -> String {
+    let text = BEARER_TOKEN.replace_all(text, "$1 [redacted]");
+
+    TELEGRAM_TOKEN.replace_all(&text, "[redacted]").into_owned()
+}
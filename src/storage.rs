@@ -0,0 +1,143 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::audit::AuditLog;
+
+/// Bot state that is not configuration, persisted next to the settings file.
+pub struct Storage {
+    data: RwLock<Data>,
+    storage_path: String,
+    audit_log: AuditLog,
+}
+
+impl Storage {
+    pub fn try_new(storage_path: String, audit_path: String) -> eyre::Result<Self> {
+        let data = confy::load_path::<Data>(storage_path.clone())?;
+
+        let storage = Self {
+            data: RwLock::new(data),
+            storage_path,
+            audit_log: AuditLog::new(audit_path),
+        };
+
+        Ok(storage)
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    /// Returns the issue created from the given Telegram message, if any.
+    pub async fn issue_for_message(&self, chat_id: i64, message_id: i32) -> Option<IssueRecord> {
+        self.data
+            .read()
+            .await
+            .issues
+            .values()
+            .find(|issue| issue.chat_id == chat_id && issue.message_id == message_id)
+            .cloned()
+    }
+
+    pub async fn issue(&self, id: &str) -> Option<IssueRecord> {
+        self.data.read().await.issues.get(id).cloned()
+    }
+
+    /// Returns the issue `number` created from the chat `chat_id`, if any.
+    pub async fn chat_issue(&self, chat_id: i64, number: u64) -> Option<IssueRecord> {
+        self.data
+            .read()
+            .await
+            .issues
+            .values()
+            .find(|issue| issue.chat_id == chat_id && issue.number == number)
+            .cloned()
+    }
+
+    pub async fn issues(&self) -> Vec<IssueRecord> {
+        self.data.read().await.issues.values().cloned().collect()
+    }
+
+    pub async fn insert_issue(&self, issue: IssueRecord) -> eyre::Result<()> {
+        self.update(|data| {
+            data.issues.insert(issue.id.clone(), issue);
+        })
+        .await
+    }
+
+    /// Sets the last known state of an issue, returning the previous one.
+    ///
+    /// Returns `None` if the issue was not created by the bot.
+    pub async fn set_issue_state(
+        &self,
+        id: &str,
+        state: &str,
+    ) -> eyre::Result<Option<Option<String>>> {
+        self.update(|data| {
+            data.issues
+                .get_mut(id)
+                .map(|issue| issue.state.replace(state.to_string()))
+        })
+        .await
+    }
+
+    /// Adds or removes `user_id` from the subscribers of an issue.
+    pub async fn set_subscription(
+        &self,
+        id: &str,
+        user_id: u64,
+        subscribed: bool,
+    ) -> eyre::Result<()> {
+        self.update(|data| {
+            if let Some(issue) = data.issues.get_mut(id) {
+                if subscribed {
+                    issue.subscribers.insert(user_id);
+                } else {
+                    issue.subscribers.remove(&user_id);
+                }
+            }
+        })
+        .await
+    }
+
+    /// Applies `f` to the data and persists it.
+    async fn update<R>(&self, f: impl FnOnce(&mut Data) -> R) -> eyre::Result<R> {
+        let mut data = self.data.write().await;
+        let result = f(&mut data);
+
+        confy::store_path(&self.storage_path, &*data)?;
+
+        Ok(result)
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Data {
+    /// Issues created by the bot, by Pylon issue id.
+    #[serde(default)]
+    pub issues: HashMap<String, IssueRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueRecord {
+    pub id: String,
+    pub number: u64,
+    #[serde(default)]
+    pub title: String,
+    pub link: String,
+    pub chat_id: i64,
+    /// Forum topic of the message, none outside of forum supergroups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_id: Option<i32>,
+    /// Message the issue was created from, 0 for messages sent privately to the bot.
+    pub message_id: i32,
+    pub created_at: DateTime<Utc>,
+    /// Last known state of the issue.
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Telegram ids of the users notified of the issue updates.
+    #[serde(default)]
+    pub subscribers: BTreeSet<u64>,
+}
@@ -0,0 +1,123 @@
+use std::{sync::Arc, time::Duration};
+
+use notify::{RecursiveMode, Watcher};
+use tokio::{
+    select,
+    sync::mpsc::unbounded_channel,
+    time::{Instant, sleep_until},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::{
+    alerts::Alerts,
+    audit::{AuditAction, AuditEntry},
+    config::Config,
+    metrics,
+    storage::Storage,
+};
+
+/// Time without changes to the settings file before it is reloaded, so that a file being written
+/// is not read half-way.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reloads the settings when their file changes.
+pub struct SettingsWatcher {
+    config: Arc<Config>,
+    storage: Arc<Storage>,
+    alerts: Alerts,
+}
+
+impl SettingsWatcher {
+    pub fn new(config: Arc<Config>, storage: Arc<Storage>, alerts: Alerts) -> Self {
+        Self {
+            config,
+            storage,
+            alerts,
+        }
+    }
+
+    pub async fn run(self, token: CancellationToken) {
+        let (tx, mut rx) = unbounded_channel();
+
+        let watcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+        })
+        .and_then(|mut watcher| {
+            watcher
+                .watch(
+                    self.config.settings_path().as_ref(),
+                    RecursiveMode::NonRecursive,
+                )
+                .map(|()| watcher)
+        });
+
+        // Dropping the watcher stops it
+        let _watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                error!("Failed to watch the settings: {err}");
+                self.alerts.send(format!(
+                    "Failed to watch the settings, they won't be reloaded: {err}"
+                ));
+                return;
+            }
+        };
+        let mut reload_at = None;
+
+        loop {
+            select! {
+                _ = token.cancelled() => {
+                    break;
+                }
+                Some(res) = rx.recv() => {
+                    match res {
+                        Ok(event) if event.kind.is_modify() => {
+                            reload_at = Some(Instant::now() + DEBOUNCE);
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            error!("Settings watch error: {err}");
+                            self.alerts.send(format!("Settings watch error: {err}"));
+                        }
+                    }
+                }
+                _ = sleep_until(reload_at.unwrap_or_else(Instant::now)), if reload_at.is_some() => {
+                    reload_at = None;
+                    self.reload().await;
+                }
+            }
+        }
+    }
+
+    async fn reload(&self) {
+        let result = self.config.reload().await;
+
+        metrics::SETTINGS_RELOADS
+            .with_label_values(&[if result.is_ok() { "success" } else { "failure" }])
+            .inc();
+
+        match result {
+            Ok(()) => {
+                info!("Settings reloaded");
+
+                let entry = AuditEntry::new(
+                    None,
+                    AuditAction::SettingsReload,
+                    self.config.settings_path(),
+                );
+
+                if let Err(err) = self.storage.audit_log().record(entry).await {
+                    self.alerts
+                        .send(format!("Failed to record settings reload: {err}"));
+                }
+            }
+            Err(err) => {
+                error!("Failed to reload settings: {err:#}");
+                self.alerts.send(format!(
+                    "Failed to reload settings, the previous ones are kept: {err:#}"
+                ));
+            }
+        }
+    }
+}
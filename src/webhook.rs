@@ -0,0 +1,100 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+};
+use chrono::Utc;
+use tokio::{net::TcpListener, sync::mpsc::UnboundedSender};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{
+    notifications::IssueEvent,
+    pylon::webhook::{
+        DELIVERY_ID_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER, WebhookDelivery, WebhookError,
+        WebhookEvent, WebhookVerifier,
+    },
+};
+
+/// Path Pylon webhooks are delivered to.
+pub const WEBHOOK_PATH: &str = "/pylon/webhook";
+
+struct WebhookState {
+    verifier: WebhookVerifier,
+    events: UnboundedSender<IssueEvent>,
+}
+
+/// Serves the Pylon webhook endpoint on `address` until `token` is cancelled.
+pub async fn serve(
+    address: SocketAddr,
+    secret: String,
+    events: UnboundedSender<IssueEvent>,
+    token: CancellationToken,
+) -> eyre::Result<()> {
+    let state = Arc::new(WebhookState {
+        verifier: WebhookVerifier::new(secret),
+        events,
+    });
+    let router = Router::new()
+        .route(WEBHOOK_PATH, post(receive))
+        .with_state(state);
+    let listener = TcpListener::bind(address).await?;
+
+    info!("Listening for Pylon webhooks on {address}");
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(token.cancelled_owned())
+        .await?;
+
+    Ok(())
+}
+
+async fn receive(
+    State(state): State<Arc<WebhookState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let delivery = WebhookDelivery {
+        id: header(DELIVERY_ID_HEADER),
+        timestamp: header(TIMESTAMP_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body: &body,
+    };
+
+    match state.verifier.verify(&delivery, Utc::now()) {
+        Ok(WebhookEvent::IssueStateChanged {
+            issue_id,
+            state: issue_state,
+        }) => {
+            let _ = state.events.send(IssueEvent::StateChanged {
+                issue_id,
+                state: issue_state,
+            });
+            StatusCode::OK
+        }
+        Ok(WebhookEvent::Unknown) => {
+            debug!("Ignored Pylon webhook {}", delivery.id);
+            StatusCode::OK
+        }
+        // Already handled, Pylon must not retry it
+        Err(WebhookError::Duplicate) => StatusCode::OK,
+        Err(err @ (WebhookError::InvalidTimestamp | WebhookError::InvalidPayload(_))) => {
+            warn!("Rejected Pylon webhook {}: {err}", delivery.id);
+            StatusCode::BAD_REQUEST
+        }
+        Err(err @ (WebhookError::InvalidSignature | WebhookError::Expired)) => {
+            warn!("Rejected Pylon webhook {}: {err}", delivery.id);
+            StatusCode::UNAUTHORIZED
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::env;
+
+use pylon_tg_bot::audit::{AuditAction, AuditEntry, AuditLog, write_csv};
+
+#[tokio::test]
+async fn test_record_and_filter_entries() {
+    let path = env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+    let audit_log = AuditLog::new(&path);
+
+    audit_log
+        .record(
+            AuditEntry::new(Some(42), AuditAction::Link, "-1001234567890").details("account acme"),
+        )
+        .await
+        .unwrap();
+    audit_log
+        .record(AuditEntry::new(Some(7), AuditAction::IssueCreate, "#12"))
+        .await
+        .unwrap();
+
+    let entries = audit_log.entries().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action, AuditAction::Link);
+    assert!(entries[0].matches("ACME"));
+    assert!(entries[1].matches("issue_create"));
+    assert!(!entries[1].matches("42"));
+}
+
+#[tokio::test]
+async fn test_skip_invalid_entries() {
+    let path = env::temp_dir().join(format!("audit-invalid-{}.jsonl", std::process::id()));
+    let audit_log = AuditLog::new(&path);
+
+    audit_log
+        .record(AuditEntry::new(
+            Some(42),
+            AuditAction::Link,
+            "-1001234567890",
+        ))
+        .await
+        .unwrap();
+    std::fs::write(
+        &path,
+        std::fs::read_to_string(&path).unwrap() + "{\"timestamp\": \"2024-\n",
+    )
+    .unwrap();
+    audit_log
+        .record(AuditEntry::new(Some(7), AuditAction::IssueCreate, "#12"))
+        .await
+        .unwrap();
+
+    let entries = audit_log.entries().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].action, AuditAction::IssueCreate);
+}
+
+#[test]
+fn test_write_csv() {
+    let entry = AuditEntry::new(None, AuditAction::SettingsChange, "-100")
+        .details("rule 'Down, again' added");
+    let mut csv = Vec::new();
+
+    write_csv(std::slice::from_ref(&entry), &mut csv).unwrap();
+
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        format!(
+            "timestamp,actor,action,target,details\n{},,settings_change,-100,\"rule 'Down, again' added\"\n",
+            entry.timestamp.to_rfc3339()
+        )
+    );
+}
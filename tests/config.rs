@@ -0,0 +1,275 @@
+use std::{env, fs};
+
+use chrono::NaiveTime;
+use pylon_tg_bot::config::{QuietHours, Settings};
+
+fn load(name: &str, content: &str) -> eyre::Result<Settings> {
+    let path = env::temp_dir().join(format!("settings-{name}-{}.toml", std::process::id()));
+    fs::write(&path, content).unwrap();
+
+    let settings = Settings::load(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+
+    settings
+}
+
+#[test]
+fn test_load_valid_settings() {
+    let settings = load(
+        "valid",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+"-1001234567890" = "acme"
+"-1009876543210" = ""
+
+[tg_chats_settings."-1001234567890"]
+quiet = true
+delete_commands = true
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.pylon_account("-1001234567890"), Some("acme"));
+    assert_eq!(settings.pylon_account("-1009876543210"), None);
+
+    let chat_settings = settings.chat_settings("-1001234567890");
+    assert!(chat_settings.quiet && chat_settings.delete_commands);
+    assert!(!settings.chat_settings("-1009876543210").quiet);
+}
+
+#[test]
+fn test_reject_invalid_settings() {
+    let err = load(
+        "unknown-key",
+        r#"
+bot_admins = ["alice"]
+bot_admin = ["bob"]
+
+[tg_chats_to_pylon_accounts]
+"#,
+    )
+    .unwrap_err();
+    assert!(format!("{err:#}").contains("bot_admin"));
+
+    let err = load(
+        "invalid",
+        r#"
+bot_admins = []
+
+[tg_chats_to_pylon_accounts]
+"-100123" = "acme"
+"+-100123" = "acme"
+"general" = "acme"
+"0100" = "acme"
+"100" = "other"
+"#,
+    )
+    .unwrap_err();
+    let message = format!("{err:#}");
+
+    assert!(message.contains("bot_admins is empty"));
+    assert!(message.contains("'general' is not a number"));
+    assert!(message.contains("chat ids '0100', '100' are the same chat"));
+}
+
+#[test]
+fn test_missing_settings_are_not_created() {
+    let path = env::temp_dir().join(format!("settings-missing-{}.toml", std::process::id()));
+
+    assert!(Settings::load(path.to_str().unwrap()).is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_chat_workspaces() {
+    let mut settings = load(
+        "workspaces",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+"-100123" = "acme"
+
+[pylon_workspaces.other]
+token_env = "OTHER_PYLON_TOKEN"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.workspace("-100123"), "default");
+
+    settings.link("-100456", "other", "globex");
+    assert_eq!(settings.workspace("-100456"), "other");
+    assert_eq!(settings.pylon_account("-100456"), Some("globex"));
+    settings.validate().unwrap();
+
+    settings.link("-100789", "missing", "initech");
+    assert!(settings.validate().is_err());
+}
+
+#[test]
+fn test_chat_bots() {
+    let mut settings = load(
+        "bots",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+"-100123" = "acme"
+
+[bots.brand]
+username = "BrandSupportBot"
+token_env = "BRAND_TELOXIDE_TOKEN"
+
+[bots.other]
+token_env = "OTHER_TELOXIDE_TOKEN"
+"#,
+    )
+    .unwrap();
+
+    settings.add_chat("-100456", "brand");
+    assert_eq!(settings.bot("-100123"), "default");
+    assert_eq!(settings.bot("-100456"), "brand");
+    assert_eq!(
+        settings.bot_chats("default"),
+        vec![(&"-100123".to_string(), &"acme".to_string())]
+    );
+    assert_eq!(settings.bot_chats("brand").len(), 1);
+    settings.validate().unwrap();
+
+    settings.add_chat("-100789", "missing");
+    assert!(settings.validate().is_err());
+}
+
+#[test]
+fn test_topic_settings() {
+    let settings = load(
+        "topics",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+"-100123" = "acme"
+
+[tg_chats_settings."-100123".topics.42]
+account_id = "acme-billing"
+tags = ["billing"]
+
+[tg_chats_settings."-100123".topics.43]
+tags = ["onboarding"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(settings.topic_account("-100123", None), Some("acme"));
+    assert_eq!(
+        settings.topic_account("-100123", Some(42)),
+        Some("acme-billing")
+    );
+    assert_eq!(settings.topic_account("-100123", Some(43)), Some("acme"));
+    assert_eq!(settings.topic_account("-100456", Some(42)), None);
+    assert_eq!(
+        settings.topic_settings("-100123", Some(43)).unwrap().tags,
+        vec!["onboarding"]
+    );
+
+    let defaults = settings.chat_settings("-100123").defaults;
+    assert_eq!(
+        defaults.body("Login broken", "bob", "ACME", "Billing"),
+        "Login broken\n\nTopic: Billing"
+    );
+    assert_eq!(
+        defaults.body("Login broken", "bob", "ACME", ""),
+        "Login broken"
+    );
+
+    let invalid = load(
+        "invalid-topic",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+
+[tg_chats_settings."-100123".topics.general]
+tags = ["general"]
+"#,
+    );
+    assert!(
+        format!("{:#}", invalid.unwrap_err())
+            .contains("topic id 'general' of chat -100123 is not a number")
+    );
+}
+
+#[test]
+fn test_rule_matches() {
+    let settings = load(
+        "rules",
+        r#"
+bot_admins = ["alice"]
+team_members = ["bob"]
+
+[tg_chats_to_pylon_accounts]
+"-100123" = "acme"
+
+[[tg_chats_settings."-100123".rules]]
+name = "Production down"
+keywords = ["Production down"]
+patterns = ['rpc.*\b5\d\d\b']
+senders = "customers"
+min_length = 10
+"#,
+    )
+    .unwrap();
+
+    let rule = &settings.chat_settings("-100123").rules[0];
+
+    assert!(rule.matches("Our PRODUCTION DOWN again", Some("carol"), &settings));
+    assert!(rule.matches("rpc returned 502 twice", None, &settings));
+    assert!(!rule.matches("rpc returned 404 twice", Some("carol"), &settings));
+    assert!(!rule.matches("rpc 503", Some("carol"), &settings));
+    assert!(!rule.matches("production down", Some("bob"), &settings));
+
+    let invalid = load(
+        "invalid-rule",
+        r#"
+bot_admins = ["alice"]
+
+[tg_chats_to_pylon_accounts]
+
+[[tg_chats_settings."-100123".rules]]
+name = "Broken"
+patterns = ["(unclosed"]
+"#,
+    );
+    assert!(
+        format!("{:#}", invalid.unwrap_err())
+            .contains("pattern '(unclosed' of rule 'Broken' in chat -100123 is invalid")
+    );
+}
+
+#[test]
+fn test_quiet_hours() {
+    let time = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+
+    let night = QuietHours {
+        start: time(22, 0),
+        end: time(6, 0),
+    };
+    assert!(night.contains(time(22, 0)));
+    assert!(night.contains(time(23, 59)));
+    assert!(night.contains(time(0, 0)));
+    assert!(night.contains(time(5, 59)));
+    assert!(!night.contains(time(6, 0)));
+    assert!(!night.contains(time(12, 0)));
+    assert!(!night.contains(time(21, 59)));
+
+    let lunch = QuietHours {
+        start: time(12, 0),
+        end: time(14, 0),
+    };
+    assert!(lunch.contains(time(13, 0)));
+    assert!(!lunch.contains(time(14, 0)));
+    assert!(!lunch.contains(time(23, 0)));
+}
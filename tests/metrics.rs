@@ -0,0 +1,11 @@
+use pylon_tg_bot::metrics::{self, COMMANDS_RECEIVED};
+
+#[test]
+fn test_encode_metrics() {
+    COMMANDS_RECEIVED.with_label_values(&["/issue"]).inc();
+
+    let metrics = metrics::encode().unwrap();
+
+    assert!(metrics.contains(r#"commands_received_total{command="/issue"} 1"#));
+    assert!(metrics.contains("issues_created_total 0"));
+}
@@ -0,0 +1,37 @@
+use std::{env, fs};
+
+use pylon_tg_bot::secret::{Secret, scrub};
+
+#[test]
+fn test_secret_is_redacted() {
+    let secret = Secret::from("pylon-token".to_string());
+
+    assert_eq!(format!("{secret:?}"), "[redacted]");
+    assert_eq!(format!("{:?}", Some(&secret)), "Some([redacted])");
+    assert_eq!(secret.expose(), "pylon-token");
+}
+
+#[test]
+fn test_read_secret_file() {
+    let path = env::temp_dir().join(format!("secret-{}", std::process::id()));
+    fs::write(&path, "pylon-token\n").unwrap();
+
+    let secret = Secret::read_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(secret.expose(), "pylon-token");
+    assert!(Secret::read_file(&path).is_err());
+}
+
+#[test]
+fn test_scrub_tokens() {
+    assert_eq!(
+        scrub(r#"headers: {"authorization": "Bearer eyJhbGciOi.J9-x_y"}"#),
+        r#"headers: {"authorization": "Bearer [redacted]"}"#
+    );
+    assert_eq!(
+        scrub("GET https://api.telegram.org/bot123456789:AAHdqTcvCH1vGWJxfSeofSAs0K5PALDsaw/getMe"),
+        "GET https://api.telegram.org/bot[redacted]/getMe"
+    );
+    assert_eq!(scrub("Issue #42 created"), "Issue #42 created");
+}
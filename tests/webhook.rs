@@ -0,0 +1,128 @@
+use chrono::{TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use pylon_tg_bot::pylon::webhook::{WebhookDelivery, WebhookError, WebhookEvent, WebhookVerifier};
+use sha2::Sha256;
+
+// Deliveries are signed locally, in the format the bot expects rather than one captured from Pylon
+const SECRET: &str = "webhook-secret";
+const BODY: &str = r#"{"event":"issue.state_changed","issue_id":"issue-1","state":"closed"}"#;
+
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn delivery<'a>(id: &'a str, timestamp: &'a str, signature: &'a str) -> WebhookDelivery<'a> {
+    WebhookDelivery {
+        id,
+        timestamp,
+        signature,
+        body: BODY.as_bytes(),
+    }
+}
+
+#[test]
+fn test_verify_signed_delivery() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = now.timestamp().to_string();
+    let signature = sign(SECRET, &timestamp, BODY);
+
+    let event = verifier
+        .verify(&delivery("1", &timestamp, &signature), now)
+        .unwrap();
+
+    assert_eq!(
+        event,
+        WebhookEvent::IssueStateChanged {
+            issue_id: "issue-1".to_string(),
+            state: "closed".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_reject_invalid_signature() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = now.timestamp().to_string();
+    let signature = sign("other-secret", &timestamp, BODY);
+
+    let result = verifier.verify(&delivery("1", &timestamp, &signature), now);
+
+    assert!(matches!(result, Err(WebhookError::InvalidSignature)));
+}
+
+#[test]
+fn test_reject_replayed_delivery() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = (now - TimeDelta::minutes(10)).timestamp().to_string();
+    let signature = sign(SECRET, &timestamp, BODY);
+
+    let result = verifier.verify(&delivery("1", &timestamp, &signature), now);
+
+    assert!(matches!(result, Err(WebhookError::Expired)));
+}
+
+#[test]
+fn test_reject_duplicate_delivery() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = now.timestamp().to_string();
+    let signature = sign(SECRET, &timestamp, BODY);
+
+    assert!(
+        verifier
+            .verify(&delivery("1", &timestamp, &signature), now)
+            .is_ok()
+    );
+
+    let result = verifier.verify(&delivery("1", &timestamp, &signature), now);
+
+    assert!(matches!(result, Err(WebhookError::Duplicate)));
+}
+
+#[test]
+fn test_reject_replay_under_another_id() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = now.timestamp().to_string();
+    let signature = sign(SECRET, &timestamp, BODY);
+
+    assert!(
+        verifier
+            .verify(&delivery("1", &timestamp, &signature), now)
+            .is_ok()
+    );
+
+    // The delivery id is not signed, an attacker can change it
+    let result = verifier.verify(&delivery("2", &timestamp, &signature.to_uppercase()), now);
+
+    assert!(matches!(result, Err(WebhookError::Duplicate)));
+}
+
+#[test]
+fn test_ignore_unknown_event() {
+    let verifier = WebhookVerifier::new(SECRET);
+    let now = Utc::now();
+    let timestamp = now.timestamp().to_string();
+    let body = r#"{"event":"account.updated","account_id":"account-1"}"#;
+    let signature = sign(SECRET, &timestamp, body);
+
+    let event = verifier
+        .verify(
+            &WebhookDelivery {
+                id: "1",
+                timestamp: &timestamp,
+                signature: &signature,
+                body: body.as_bytes(),
+            },
+            now,
+        )
+        .unwrap();
+
+    assert_eq!(event, WebhookEvent::Unknown);
+}